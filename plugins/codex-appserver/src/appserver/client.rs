@@ -1,39 +1,58 @@
-//! Codex App Server client — JSON-RPC 2.0 over stdio.
+//! Codex App Server client — JSON-RPC 2.0 over a pluggable transport.
 //!
-//! Spawns `codex app-server`, sends requests/notifications on stdin,
-//! reads JSONL responses/notifications from stdout via a background reader task.
-
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+//! By default spawns `codex app-server` and talks over its stdio; any other
+//! [`Transport`] can be supplied via [`CodexAppServerClient::connect`]. Requests and
+//! notifications are serialized by callers and written as JSONL by a dedicated
+//! writer task; responses and notifications are read back by a background reader
+//! task. The client is a cheap `Clone` handle, so several tasks can issue requests
+//! and wait on different turns over the same app-server process concurrently.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use serde_json::Value;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
-use tokio::process::{Child, ChildStdin, Command};
-use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::process::Child;
+use tokio::sync::{broadcast, mpsc, oneshot, Mutex, Notify};
 use tokio::task::JoinHandle;
 
-use super::protocol::{JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, ServerMessage};
+use super::config::ClientConfig;
+use super::error::AppServerError;
+use super::events::{EventStream, ServerEvent, EVENT_CHANNEL_CAPACITY};
+use super::handlers::{RequestHandler, RequestHandlers};
+use super::protocol::{
+    InitializeParams, InitializeResponse, JsonRpcNotification, JsonRpcReply, JsonRpcRequest,
+    JsonRpcResponse, ModelListParams, ModelListResponse, RequestId, ServerMessage,
+    ThreadResumeParams, ThreadStartParams, ThreadStartResponse, TurnCompletedParams,
+    TurnInterruptParams, TurnStartParams, TurnStartResponse,
+};
+use super::recorder::{Direction, Recorder};
+use super::retry::RetryPolicy;
+use super::stderr::{capture_stderr, StderrBuffer, ERROR_STDERR_TAIL_LINES};
+use super::transport::{BoxedReader, BoxedWriter, ChildStdioTransport, Transport, TransportParts};
 
-/// Maximum accumulated agent text size (16 MB).
-const MAX_AGENT_TEXT_BYTES: usize = 16 * 1024 * 1024;
+/// Maximum bytes to log from a parse-error line (avoids leaking sensitive content).
+const MAX_LOG_LINE_BYTES: usize = 200;
 
-/// Default timeout for JSON-RPC requests (60 seconds).
-const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Maximum `turn/completed` notifications kept for waiters that have not claimed
+/// them yet. Beyond this the oldest unclaimed completion is discarded.
+const MAX_PENDING_TURN_COMPLETIONS: usize = 64;
 
-/// Maximum bytes per line read from stdout (32 MB).
-/// Lines exceeding this are truncated to prevent OOM from malformed output.
-const MAX_LINE_BYTES: usize = 32 * 1024 * 1024;
+/// How long a `ServerExited` error waits for the rest of stderr (crash messages
+/// often arrive just after stdout closes).
+const STDERR_DRAIN_GRACE: Duration = Duration::from_millis(250);
 
-/// Maximum bytes to log from a parse-error line (avoids leaking sensitive content).
-const MAX_LOG_LINE_BYTES: usize = 200;
+/// How long dropping the last client handle waits for the transcript writer.
+const RECORDER_DROP_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Shutdown result reporting what happened during teardown.
 #[derive(Debug)]
 pub struct ShutdownStatus {
     pub shutdown_request: Result<(), String>,
     pub exit_notify: Result<(), String>,
+    /// Always `true` for transports without a child process.
     pub process_exited: bool,
 }
 
@@ -43,160 +62,212 @@ impl ShutdownStatus {
     }
 }
 
+/// Work item for the writer task.
+enum WriterCommand {
+    /// Write one JSONL line. `ack` (if any) receives the write result.
+    Line {
+        line: String,
+        ack: Option<oneshot::Sender<Result<(), AppServerError>>>,
+    },
+    /// Shut down the write half (signals EOF to socket/in-memory peers).
+    Close { ack: oneshot::Sender<()> },
+}
+
+/// Identifies a turn: (thread id, turn id). Ids missing from a notification are empty.
+type TurnKey = (String, String);
+
+/// State shared between client handles and the background reader.
+struct Shared {
+    response_map: Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>,
+    handlers: Mutex<RequestHandlers>,
+    /// Completed turns not yet claimed by `wait_turn_completed`.
+    completed_turns: Mutex<VecDeque<Value>>,
+    /// Woken whenever a turn completes or the connection closes.
+    turn_notify: Notify,
+    /// Agent message text accumulated per (thread id, turn id).
+    turn_outputs: Mutex<HashMap<TurnKey, String>>,
+    /// Set by the reader once the server closes the connection.
+    closed: AtomicBool,
+    max_agent_text_bytes: usize,
+}
+
+/// Owned resources behind every client handle; dropped with the last handle.
+struct ClientInner {
+    shared: Arc<Shared>,
+    outgoing: mpsc::UnboundedSender<WriterCommand>,
+    child: Mutex<Option<Child>>,
+    request_timeout: Duration,
+    retry: RetryPolicy,
+    events_tx: broadcast::WeakSender<ServerEvent>,
+    /// Captured server stderr, for transports that provide one.
+    stderr: Option<StderrBuffer>,
+    /// Result of the last successful `initialize`.
+    server_info: std::sync::Mutex<Option<InitializeResponse>>,
+    next_id: AtomicU64,
+    /// Protocol transcript, when recording.
+    recorder: Option<Arc<Recorder>>,
+    reader_task: JoinHandle<()>,
+    writer_task: JoinHandle<()>,
+    stderr_task: Option<JoinHandle<()>>,
+}
+
+impl Drop for ClientInner {
+    fn drop(&mut self) {
+        // kill_on_drop(true) handles process cleanup.
+        // Abort the background tasks to prevent leaked work.
+        self.reader_task.abort();
+        self.writer_task.abort();
+        if let Some(task) = &self.stderr_task {
+            task.abort();
+        }
+        // The process may exit right after; get what was exchanged on disk first.
+        if let Some(recorder) = &self.recorder {
+            recorder.flush_blocking(RECORDER_DROP_FLUSH_TIMEOUT);
+        }
+    }
+}
+
 /// Client for communicating with a `codex app-server` process.
+///
+/// Cloning yields another handle to the same connection; all methods take `&self`.
+#[derive(Clone)]
 pub struct CodexAppServerClient {
-    child: Child,
-    stdin: BufWriter<ChildStdin>,
-    response_map: Arc<Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>>,
-    turn_completed_rx: mpsc::UnboundedReceiver<Value>,
-    agent_text: Arc<Mutex<String>>,
-    next_id: AtomicU64,
-    _reader_task: JoinHandle<()>,
+    inner: Arc<ClientInner>,
 }
 
 impl CodexAppServerClient {
-    /// Spawn the `codex app-server` process and start the background reader.
-    pub async fn spawn() -> Result<Self, String> {
-        let mut child = Command::new("codex")
-            .arg("app-server")
-            .kill_on_drop(true)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::inherit())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn codex app-server: {e}"))?;
-
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or("Failed to capture stdin")?;
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or("Failed to capture stdout")?;
-
-        let stdin = BufWriter::new(stdin);
-        let response_map: Arc<Mutex<HashMap<u64, oneshot::Sender<JsonRpcResponse>>>> =
-            Arc::new(Mutex::new(HashMap::new()));
-        let agent_text: Arc<Mutex<String>> = Arc::new(Mutex::new(String::new()));
-        // Unbounded channel: reader must never block on notification dispatch.
-        let (turn_completed_tx, turn_completed_rx) = mpsc::unbounded_channel::<Value>();
-
-        // Background reader task: reads JSONL from stdout, dispatches messages.
-        let reader_response_map = response_map.clone();
-        let reader_agent_text = agent_text.clone();
-        let reader_task = tokio::spawn(async move {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
-
-            loop {
-                // Read next line with size guard.
-                let line = match read_line_bounded(&mut lines, MAX_LINE_BYTES).await {
-                    Some(Ok(line)) => line,
-                    Some(Err(e)) => {
-                        eprintln!("[appserver-reader] read error: {e}");
-                        continue;
-                    }
-                    None => break, // EOF
-                };
+    /// Spawn `codex app-server` (honouring `CODEX_BIN`) and start the background tasks.
+    pub async fn spawn() -> Result<Self, AppServerError> {
+        Self::spawn_with_config(&ClientConfig::from_env()).await
+    }
 
-                if line.is_empty() {
-                    continue;
-                }
+    /// Spawn the app server as described by `config` and start the background tasks.
+    pub async fn spawn_with_config(config: &ClientConfig) -> Result<Self, AppServerError> {
+        let transport = ChildStdioTransport::spawn(config.command())?;
+        Ok(Self::connect_with_config(transport, config))
+    }
 
-                match ServerMessage::parse(&line) {
-                    Ok(ServerMessage::Response(resp)) => {
-                        if let Some(id) = resp.id {
-                            let mut map = reader_response_map.lock().await;
-                            if let Some(tx) = map.remove(&id) {
-                                let _ = tx.send(resp);
-                            }
-                        }
-                    }
-                    Ok(ServerMessage::Notification { method, params }) => {
-                        match method.as_str() {
-                            "item/agentMessage/delta" => {
-                                if let Some(delta) =
-                                    params.get("delta").and_then(|d| d.as_str())
-                                {
-                                    let mut text = reader_agent_text.lock().await;
-                                    let remaining =
-                                        MAX_AGENT_TEXT_BYTES.saturating_sub(text.len());
-                                    if remaining > 0 {
-                                        let take =
-                                            truncate_to_char_boundary(delta, remaining);
-                                        text.push_str(take);
-                                    }
-                                }
-                            }
-                            "turn/completed" => {
-                                let _ = turn_completed_tx.send(params);
-                            }
-                            _ => {}
-                        }
-                    }
-                    Err(e) => {
-                        let redacted = truncate_to_char_boundary(&line, MAX_LOG_LINE_BYTES);
-                        eprintln!(
-                            "[appserver-reader] parse error: {e} — line[..{}]: {redacted}",
-                            redacted.len()
-                        );
-                    }
-                }
-            }
+    /// Start the background reader and writer over an arbitrary transport,
+    /// with default limits.
+    pub fn connect(transport: impl Transport) -> Self {
+        Self::connect_with_config(transport, &ClientConfig::default())
+    }
 
-            // EOF: server exited. Drain all pending request senders so callers
-            // get an error instead of waiting forever.
-            let mut map = reader_response_map.lock().await;
-            for (_id, tx) in map.drain() {
-                let _ = tx.send(JsonRpcResponse {
-                    id: None,
-                    result: None,
-                    error: Some(super::protocol::JsonRpcError {
-                        code: -1,
-                        message: "App server exited".to_string(),
-                        data: None,
-                    }),
-                });
-            }
+    /// Start the background reader and writer over an arbitrary transport.
+    /// Spawn settings of `config` are ignored; limits, timeouts, stderr capture and
+    /// transcript settings apply. A transcript file that cannot be opened is
+    /// reported on stderr and the client runs without recording.
+    pub fn connect_with_config(transport: impl Transport, config: &ClientConfig) -> Self {
+        let TransportParts {
+            reader,
+            writer,
+            child,
+            stderr,
+        } = transport.into_parts();
+
+        let shared = Arc::new(Shared {
+            response_map: Mutex::new(HashMap::new()),
+            handlers: Mutex::new(RequestHandlers::with_defaults()),
+            completed_turns: Mutex::new(VecDeque::new()),
+            turn_notify: Notify::new(),
+            turn_outputs: Mutex::new(HashMap::new()),
+            closed: AtomicBool::new(false),
+            max_agent_text_bytes: config.max_agent_text_bytes,
         });
+        let recorder = config.transcript.as_deref().and_then(|path| {
+            Recorder::create(path)
+                .map_err(|e| eprintln!("[appserver-recorder] recording disabled: {e}"))
+                .ok()
+                .map(Arc::new)
+        });
+        // Unbounded channel: neither callers nor the reader block on queued writes.
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel::<WriterCommand>();
+        // Broadcast never blocks the sender; slow subscribers lag instead. The reader
+        // owns the only strong sender, so subscriptions end when the reader exits.
+        let (reader_events_tx, _) = broadcast::channel::<ServerEvent>(EVENT_CHANNEL_CAPACITY);
+        let events_tx = reader_events_tx.downgrade();
+
+        let writer_task = tokio::spawn(run_writer(writer, outgoing_rx, recorder.clone()));
+        let client_recorder = recorder.clone();
+        let reader_task = tokio::spawn(run_reader(
+            reader,
+            config.max_line_bytes,
+            shared.clone(),
+            outgoing.clone(),
+            reader_events_tx,
+            recorder,
+        ));
+        let (stderr, stderr_task) = match stderr {
+            Some(reader) => {
+                let buffer = StderrBuffer::new(config.stderr_tail_bytes);
+                let task = tokio::spawn(capture_stderr(
+                    reader,
+                    buffer.clone(),
+                    config.stderr_log.clone(),
+                    config.echo_stderr,
+                ));
+                (Some(buffer), Some(task))
+            }
+            None => (None, None),
+        };
 
-        Ok(Self {
-            child,
-            stdin,
-            response_map,
-            turn_completed_rx,
-            agent_text,
-            next_id: AtomicU64::new(1),
-            _reader_task: reader_task,
-        })
-    }
-
-    /// Send a JSON-RPC request and wait for the matching response (with timeout).
-    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value, String> {
-        self.request_with_timeout(method, params, REQUEST_TIMEOUT)
+        Self {
+            inner: Arc::new(ClientInner {
+                shared,
+                outgoing,
+                child: Mutex::new(child),
+                request_timeout: config.request_timeout,
+                retry: config.retry.clone(),
+                events_tx,
+                stderr,
+                server_info: std::sync::Mutex::new(None),
+                next_id: AtomicU64::new(1),
+                recorder: client_recorder,
+                reader_task,
+                writer_task,
+                stderr_task,
+            }),
+        }
+    }
+
+    /// Whether the server has closed the connection.
+    pub fn is_closed(&self) -> bool {
+        self.inner.shared.closed.load(Ordering::SeqCst)
+    }
+
+    /// Send a JSON-RPC request and wait for the matching response, using the
+    /// configured request timeout.
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, AppServerError> {
+        self.request_with_timeout(method, params, self.inner.request_timeout)
             .await
     }
 
     /// Send a JSON-RPC request with a custom timeout.
     pub async fn request_with_timeout(
-        &mut self,
+        &self,
         method: &str,
         params: Value,
         timeout: Duration,
-    ) -> Result<Value, String> {
-        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+    ) -> Result<Value, AppServerError> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
         let req = JsonRpcRequest::new(id, method, params);
+        let response_map = &self.inner.shared.response_map;
 
         let (tx, rx) = oneshot::channel();
         {
-            let mut map = self.response_map.lock().await;
+            let mut map = response_map.lock().await;
             map.insert(id, tx);
         }
+        // The reader marks the connection closed before dropping pending senders, so
+        // checking after registering means a dead server is never waited on.
+        if self.is_closed() {
+            response_map.lock().await.remove(&id);
+            return Err(self.server_exited().await);
+        }
 
         // On write failure, clean up the pending sender before returning.
         if let Err(e) = self.send_line(&req).await {
-            let mut map = self.response_map.lock().await;
+            let mut map = response_map.lock().await;
             map.remove(&id);
             return Err(e);
         }
@@ -204,116 +275,354 @@ impl CodexAppServerClient {
         // Wait with timeout to prevent deadlock if server stops responding.
         let resp = match tokio::time::timeout(timeout, rx).await {
             Ok(Ok(resp)) => resp,
-            Ok(Err(_)) => return Err("Response channel closed".to_string()),
+            // Reader dropped the sender: the server went away.
+            Ok(Err(_)) => return Err(self.server_exited().await),
             Err(_) => {
                 // Timeout: deterministically clean up the stale sender.
-                let mut map = self.response_map.lock().await;
+                let mut map = response_map.lock().await;
                 map.remove(&id);
-                return Err(format!(
-                    "Request '{method}' timed out after {}s",
-                    timeout.as_secs()
-                ));
+                return Err(AppServerError::Timeout {
+                    operation: format!("response to '{method}'"),
+                    after: timeout,
+                    stderr_tail: self.stderr_tail(ERROR_STDERR_TAIL_LINES),
+                });
             }
         };
 
-        if let Some(err) = resp.error {
-            return Err(err.to_string());
+        if let Some(error) = resp.error {
+            return Err(AppServerError::Rpc {
+                method: method.to_string(),
+                error,
+            });
         }
         Ok(resp.result.unwrap_or(Value::Null))
     }
 
     /// Send a JSON-RPC notification (no response expected).
-    pub async fn notify(&mut self, method: &str, params: Value) -> Result<(), String> {
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), AppServerError> {
         let notif = JsonRpcNotification::new(method, params);
         self.send_line(&notif).await
     }
 
-    /// Serialize and write a value as a JSONL line to stdin.
-    async fn send_line(&mut self, value: &impl serde::Serialize) -> Result<(), String> {
-        let line = serde_json::to_string(value).map_err(|e| format!("Serialize error: {e}"))?;
-        self.stdin
-            .write_all(line.as_bytes())
+    /// Send a request with typed params and decode its typed result. A result that
+    /// does not match `R` is a `Protocol` error naming the method.
+    pub async fn call<P, R>(&self, method: &str, params: &P) -> Result<R, AppServerError>
+    where
+        P: serde::Serialize,
+        R: serde::de::DeserializeOwned,
+    {
+        let result = self.request(method, serde_json::to_value(params)?).await?;
+        serde_json::from_value(result)
+            .map_err(|e| AppServerError::Protocol(format!("Invalid '{method}' result: {e}")))
+    }
+
+    /// [`call`](Self::call) for requests that are safe to repeat: errors the retry
+    /// policy deems transient are retried with backoff, each retry logged. When the
+    /// attempts run out the last error is wrapped in `RetriesExhausted`.
+    pub async fn call_idempotent<P, R>(&self, method: &str, params: &P) -> Result<R, AppServerError>
+    where
+        P: serde::Serialize,
+        R: serde::de::DeserializeOwned,
+    {
+        let policy = &self.inner.retry;
+        let mut attempt = 1;
+        loop {
+            let err = match self.call(method, params).await {
+                Ok(result) => return Ok(result),
+                Err(err) if policy.is_retryable(&err) => err,
+                Err(err) => return Err(err),
+            };
+            if attempt >= policy.max_attempts {
+                return Err(if attempt == 1 {
+                    err
+                } else {
+                    AppServerError::RetriesExhausted {
+                        attempts: attempt,
+                        last: Box::new(err),
+                    }
+                });
+            }
+            let delay = policy.delay(attempt, &err);
+            eprintln!(
+                "[appserver-retry] {err}; retrying in {}ms ({attempt}/{})",
+                delay.as_millis(),
+                policy.max_attempts - 1
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Run the connection handshake: the `initialize` request followed by the
+    /// `initialized` notification. The result is kept; see [`Self::server_info`].
+    pub async fn initialize(
+        &self,
+        params: &InitializeParams,
+    ) -> Result<InitializeResponse, AppServerError> {
+        let result: InitializeResponse = self.call_idempotent("initialize", params).await?;
+        *self.inner.server_info.lock().unwrap() = Some(result.clone());
+        self.notify("initialized", Value::Null).await?;
+        Ok(result)
+    }
+
+    /// What the server reported about itself in `initialize`, or `None` before the
+    /// handshake. Use [`ServerFeatures::negotiate`](super::ServerFeatures::negotiate)
+    /// to check the version.
+    pub fn server_info(&self) -> Option<InitializeResponse> {
+        self.inner.server_info.lock().unwrap().clone()
+    }
+
+    /// Start a new thread (`thread/start`).
+    pub async fn thread_start(
+        &self,
+        params: &ThreadStartParams,
+    ) -> Result<ThreadStartResponse, AppServerError> {
+        self.call_idempotent("thread/start", params).await
+    }
+
+    /// List the models the server can run (`model/list`).
+    pub async fn model_list(
+        &self,
+        params: &ModelListParams,
+    ) -> Result<ModelListResponse, AppServerError> {
+        self.call_idempotent("model/list", params).await
+    }
+
+    /// Reopen an existing thread by id (`thread/resume`).
+    pub async fn thread_resume(
+        &self,
+        params: &ThreadResumeParams,
+    ) -> Result<ThreadStartResponse, AppServerError> {
+        self.call("thread/resume", params).await
+    }
+
+    /// Start a turn on a thread (`turn/start`). Await its end with
+    /// [`wait_for_turn`](Self::wait_for_turn).
+    pub async fn turn_start(
+        &self,
+        params: &TurnStartParams,
+    ) -> Result<TurnStartResponse, AppServerError> {
+        self.call("turn/start", params).await
+    }
+
+    /// Serialize a value and hand it to the writer task; resolves once it is flushed.
+    /// Writing to a server that has gone away fails with `ServerExited`, not `Io`.
+    async fn send_line(&self, value: &impl serde::Serialize) -> Result<(), AppServerError> {
+        if self.is_closed() {
+            return Err(self.server_exited().await);
+        }
+        let line = serde_json::to_string(value)?;
+        let (ack, ack_rx) = oneshot::channel();
+        let command = WriterCommand::Line {
+            line,
+            ack: Some(ack),
+        };
+        if self.inner.outgoing.send(command).is_err() {
+            return Err(self.server_exited().await);
+        }
+        match ack_rx.await {
+            Ok(Err(AppServerError::Io { source, .. }))
+                if is_disconnect(&source) || self.is_closed() =>
+            {
+                Err(self.server_exited().await)
+            }
+            Ok(result) => result,
+            Err(_) => Err(self.server_exited().await),
+        }
+    }
+
+    /// Ask the server to stop a running turn. The turn still ends with a
+    /// `turn/completed` notification (status `interrupted`), which callers can await.
+    pub async fn turn_interrupt(
+        &self,
+        thread_id: &str,
+        turn_id: &str,
+    ) -> Result<(), AppServerError> {
+        let params = TurnInterruptParams {
+            thread_id: thread_id.to_string(),
+            turn_id: turn_id.to_string(),
+        };
+        self.call::<_, Value>("turn/interrupt", &params)
             .await
-            .map_err(|e| format!("Write error: {e}"))?;
-        self.stdin
-            .write_all(b"\n")
+            .map(|_| ())
+    }
+
+    /// Build a `ServerExited` error, with the child's exit status when it is known
+    /// and the end of its stderr.
+    async fn server_exited(&self) -> AppServerError {
+        if let Some(buffer) = &self.inner.stderr {
+            buffer.wait_closed(STDERR_DRAIN_GRACE).await;
+        }
+        let status = self
+            .inner
+            .child
+            .lock()
             .await
-            .map_err(|e| format!("Write newline error: {e}"))?;
-        self.stdin
-            .flush()
+            .as_mut()
+            .and_then(|child| child.try_wait().ok().flatten());
+        AppServerError::ServerExited {
+            status,
+            stderr_tail: self.stderr_tail(ERROR_STDERR_TAIL_LINES),
+        }
+    }
+
+    /// The last `max_lines` lines the server wrote to stderr, or `None` if nothing
+    /// was captured (or the transport has no stderr).
+    pub fn stderr_tail(&self, max_lines: usize) -> Option<String> {
+        self.inner.stderr.as_ref()?.tail(max_lines)
+    }
+
+    /// Register (or replace) the handler answering server requests for `method`.
+    /// By default approval requests are declined; see [`RequestHandlers::with_defaults`].
+    pub async fn set_request_handler(&self, method: impl Into<String>, handler: RequestHandler) {
+        self.inner
+            .shared
+            .handlers
+            .lock()
             .await
-            .map_err(|e| format!("Flush error: {e}"))?;
-        Ok(())
+            .insert(method, handler);
+    }
+
+    /// Remove the handler for `method`; subsequent requests get "method not found".
+    pub async fn remove_request_handler(&self, method: &str) -> Option<RequestHandler> {
+        self.inner.shared.handlers.lock().await.remove(method)
     }
 
     /// Wait for a `turn/completed` notification matching a specific turn,
-    /// with a timeout. Stale completions for other turns are discarded.
+    /// with a timeout. Completions for other turns are left for their own waiters
+    /// (bounded by `MAX_PENDING_TURN_COMPLETIONS`), so several turns can be awaited
+    /// concurrently. With no turn ID, the oldest unclaimed completion is returned.
     pub async fn wait_turn_completed(
-        &mut self,
+        &self,
         expected_turn_id: Option<&str>,
         timeout: Duration,
-    ) -> Result<Value, String> {
+    ) -> Result<Value, AppServerError> {
         let deadline = tokio::time::Instant::now() + timeout;
+        let shared = &self.inner.shared;
 
         loop {
-            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
-            if remaining.is_zero() {
-                return Err("Timeout waiting for turn/completed".to_string());
-            }
-
-            let params = tokio::time::timeout(remaining, self.turn_completed_rx.recv())
-                .await
-                .map_err(|_| "Timeout waiting for turn/completed".to_string())?
-                .ok_or_else(|| "turn/completed channel closed".to_string())?;
+            // Register for wakeups before checking state so none are missed.
+            let notified = shared.turn_notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
 
-            // If no specific turn ID requested, accept any completion.
-            let Some(expected) = expected_turn_id else {
+            if let Some(params) = self.take_turn_completion(expected_turn_id).await {
                 return Ok(params);
-            };
+            }
+            if self.is_closed() {
+                return Err(self.server_exited().await);
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return Err(AppServerError::Timeout {
+                    operation: "turn/completed".to_string(),
+                    after: timeout,
+                    stderr_tail: self.stderr_tail(ERROR_STDERR_TAIL_LINES),
+                });
+            }
+        }
+    }
 
-            // Check if this completion matches the expected turn.
-            let actual_id = params
-                .get("turn")
-                .and_then(|t| t.get("id"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
+    /// Typed form of [`wait_turn_completed`](Self::wait_turn_completed) for one turn.
+    pub async fn wait_for_turn(
+        &self,
+        turn_id: &str,
+        timeout: Duration,
+    ) -> Result<TurnCompletedParams, AppServerError> {
+        let params = self.wait_turn_completed(Some(turn_id), timeout).await?;
+        serde_json::from_value(params)
+            .map_err(|e| AppServerError::Protocol(format!("Invalid 'turn/completed' params: {e}")))
+    }
 
-            if actual_id == expected {
-                return Ok(params);
-            }
+    /// Claim the oldest completion for `expected_turn_id` (or any turn if `None`).
+    async fn take_turn_completion(&self, expected_turn_id: Option<&str>) -> Option<Value> {
+        let mut pending = self.inner.shared.completed_turns.lock().await;
+        let position = match expected_turn_id {
+            None => 0,
+            Some(expected) => pending.iter().position(|params| {
+                params
+                    .get("turn")
+                    .and_then(|t| t.get("id"))
+                    .and_then(|v| v.as_str())
+                    == Some(expected)
+            })?,
+        };
+        pending.remove(position)
+    }
 
-            // Stale completion — discard and keep waiting.
-            eprintln!(
-                "[appserver] discarding stale turn/completed (expected={expected}, got={actual_id})"
-            );
-        }
+    /// Subscribe to typed events decoded from server notifications.
+    ///
+    /// Only events received after this call are delivered, so subscribe before
+    /// starting the turn you want to observe. The stream ends when the server exits.
+    pub fn subscribe(&self) -> EventStream {
+        EventStream::new(self.inner.events_tx.upgrade().map(|tx| tx.subscribe()))
+    }
+
+    /// Agent text accumulated so far from `item/agentMessage/delta` notifications
+    /// of one turn, or `None` if that turn has produced no text yet.
+    pub async fn turn_output(&self, thread_id: &str, turn_id: &str) -> Option<String> {
+        let key = (thread_id.to_string(), turn_id.to_string());
+        self.inner
+            .shared
+            .turn_outputs
+            .lock()
+            .await
+            .get(&key)
+            .cloned()
     }
 
-    /// Get the accumulated agent text from all `item/agentMessage/delta` notifications.
-    pub async fn accumulated_text(&self) -> String {
-        self.agent_text.lock().await.clone()
+    /// Like [`turn_output`](Self::turn_output), but also releases the stored text.
+    /// Call once a turn is finished so long sessions do not keep every output.
+    pub async fn take_turn_output(&self, thread_id: &str, turn_id: &str) -> Option<String> {
+        let key = (thread_id.to_string(), turn_id.to_string());
+        self.inner.shared.turn_outputs.lock().await.remove(&key)
     }
 
-    /// Clear the accumulated agent text (useful between turns).
-    pub async fn clear_text(&self) {
-        self.agent_text.lock().await.clear();
+    /// Wait until every line exchanged so far is in the protocol transcript. The
+    /// transcript is written in the background; without recording this returns at once.
+    pub async fn flush_transcript(&self) {
+        if let Some(recorder) = &self.inner.recorder {
+            recorder.flush().await;
+        }
     }
 
     /// Gracefully shut down the app server. Returns status of each teardown step.
-    pub async fn shutdown(mut self) -> ShutdownStatus {
+    /// Other clones of this handle see a closed connection afterwards.
+    pub async fn shutdown(self) -> ShutdownStatus {
         let shutdown_timeout = Duration::from_secs(5);
 
         let shutdown_request = self
             .request_with_timeout("shutdown", Value::Null, shutdown_timeout)
             .await
-            .map(|_| ());
+            .map(|_| ())
+            .map_err(|e| e.to_string());
 
-        let exit_notify = self.notify("exit", Value::Null).await;
+        let exit_notify = self
+            .notify("exit", Value::Null)
+            .await
+            .map_err(|e| e.to_string());
 
-        let process_exited =
-            tokio::time::timeout(shutdown_timeout, self.child.wait())
+        // Transports without a child process have nothing further to wait on.
+        let mut child = self.inner.child.lock().await;
+        let process_exited = match child.as_mut() {
+            Some(child) => tokio::time::timeout(shutdown_timeout, child.wait())
                 .await
-                .is_ok();
+                .is_ok(),
+            None => {
+                let (ack, ack_rx) = oneshot::channel();
+                if self
+                    .inner
+                    .outgoing
+                    .send(WriterCommand::Close { ack })
+                    .is_ok()
+                {
+                    let _ = ack_rx.await;
+                }
+                true
+            }
+        };
+
+        drop(child);
+        self.flush_transcript().await;
 
         ShutdownStatus {
             shutdown_request,
@@ -323,19 +632,168 @@ impl CodexAppServerClient {
     }
 }
 
-impl Drop for CodexAppServerClient {
-    fn drop(&mut self) {
-        // kill_on_drop(true) handles process cleanup.
-        // Abort the reader task to prevent leaked background work.
-        self._reader_task.abort();
+/// Writer task: drains queued lines to the transport, flushing after each one.
+async fn run_writer(
+    writer: BoxedWriter,
+    mut commands: mpsc::UnboundedReceiver<WriterCommand>,
+    recorder: Option<Arc<Recorder>>,
+) {
+    let mut writer = BufWriter::new(writer);
+    while let Some(command) = commands.recv().await {
+        match command {
+            WriterCommand::Line { line, ack } => {
+                if let Some(recorder) = &recorder {
+                    recorder.record(Direction::Send, &line);
+                }
+                let result = write_line(&mut writer, &line).await;
+                if let Some(ack) = ack {
+                    let _ = ack.send(result);
+                }
+            }
+            WriterCommand::Close { ack } => {
+                let _ = writer.shutdown().await;
+                let _ = ack.send(());
+            }
+        }
+    }
+}
+
+/// Reader task: reads JSONL from the transport and dispatches each message.
+async fn run_reader(
+    reader: BoxedReader,
+    max_line_bytes: usize,
+    shared: Arc<Shared>,
+    outgoing: mpsc::UnboundedSender<WriterCommand>,
+    events_tx: broadcast::Sender<ServerEvent>,
+    recorder: Option<Arc<Recorder>>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+
+    loop {
+        // Read next line with size guard.
+        let line = match read_line_bounded(&mut lines, max_line_bytes).await {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => {
+                eprintln!("[appserver-reader] read error: {e}");
+                continue;
+            }
+            None => break, // EOF
+        };
+
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(recorder) = &recorder {
+            recorder.record(Direction::Recv, &line);
+        }
+
+        match ServerMessage::parse(&line) {
+            Ok(ServerMessage::Response(resp)) => {
+                if let Some(RequestId::Number(id)) = resp.id {
+                    let mut map = shared.response_map.lock().await;
+                    if let Some(tx) = map.remove(&id) {
+                        let _ = tx.send(resp);
+                    }
+                }
+            }
+            Ok(ServerMessage::Request { id, method, params }) => {
+                // Answer every server request so the turn never waits on us.
+                let outcome = shared.handlers.lock().await.dispatch(&method, &params);
+                if let Err(e) = &outcome {
+                    eprintln!("[appserver-reader] rejecting server request '{method}': {e}");
+                }
+                let reply = JsonRpcReply::new(id, outcome);
+                match serde_json::to_string(&reply) {
+                    Ok(line) => {
+                        let _ = outgoing.send(WriterCommand::Line { line, ack: None });
+                    }
+                    Err(e) => eprintln!("[appserver-reader] failed to answer '{method}': {e}"),
+                }
+            }
+            Ok(ServerMessage::Notification { method, params }) => {
+                if events_tx.receiver_count() > 0 {
+                    let _ = events_tx.send(ServerEvent::from_notification(&method, &params));
+                }
+                match method.as_str() {
+                    "item/agentMessage/delta" => {
+                        if let Some(delta) = params.get("delta").and_then(|d| d.as_str()) {
+                            let id = |key: &str| params[key].as_str().unwrap_or("").to_string();
+                            let mut outputs = shared.turn_outputs.lock().await;
+                            let text = outputs.entry((id("threadId"), id("turnId"))).or_default();
+                            let remaining = shared.max_agent_text_bytes.saturating_sub(text.len());
+                            if remaining > 0 {
+                                let take = truncate_to_char_boundary(delta, remaining);
+                                text.push_str(take);
+                            }
+                        }
+                    }
+                    "turn/completed" => {
+                        let mut pending = shared.completed_turns.lock().await;
+                        if pending.len() >= MAX_PENDING_TURN_COMPLETIONS {
+                            if let Some(stale) = pending.pop_front() {
+                                eprintln!(
+                                    "[appserver-reader] discarding unclaimed turn/completed (turn={})",
+                                    stale["turn"]["id"].as_str().unwrap_or("")
+                                );
+                            }
+                        }
+                        pending.push_back(params);
+                        drop(pending);
+                        shared.turn_notify.notify_waiters();
+                    }
+                    _ => {}
+                }
+            }
+            Err(e) => {
+                let redacted = truncate_to_char_boundary(&line, MAX_LOG_LINE_BYTES);
+                eprintln!(
+                    "[appserver-reader] parse error: {e} — line[..{}]: {redacted}",
+                    redacted.len()
+                );
+            }
+        }
     }
+
+    // EOF: server exited. Drop all pending request senders so callers
+    // get `ServerExited` instead of waiting forever, and wake turn waiters.
+    shared.closed.store(true, Ordering::SeqCst);
+    shared.response_map.lock().await.clear();
+    shared.turn_notify.notify_waiters();
+}
+
+/// Whether a write failed because the other end of the connection is gone.
+fn is_disconnect(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::NotConnected
+    )
+}
+
+/// Write one JSONL line and flush immediately.
+async fn write_line(writer: &mut BufWriter<BoxedWriter>, line: &str) -> Result<(), AppServerError> {
+    writer
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| AppServerError::io("Write error", e))?;
+    writer
+        .write_all(b"\n")
+        .await
+        .map_err(|e| AppServerError::io("Write newline error", e))?;
+    writer
+        .flush()
+        .await
+        .map_err(|e| AppServerError::io("Flush error", e))?;
+    Ok(())
 }
 
 /// Read a line from a `Lines` stream, enforcing a maximum byte length.
 /// Returns `None` on EOF, `Some(Err)` on read error, `Some(Ok(line))` on success.
 /// Lines exceeding `max_bytes` are truncated at a UTF-8 boundary.
-async fn read_line_bounded(
-    lines: &mut tokio::io::Lines<BufReader<tokio::process::ChildStdout>>,
+async fn read_line_bounded<R: AsyncBufRead + Unpin>(
+    lines: &mut tokio::io::Lines<R>,
     max_bytes: usize,
 ) -> Option<Result<String, std::io::Error>> {
     match lines.next_line().await {
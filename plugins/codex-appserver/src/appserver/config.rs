@@ -0,0 +1,269 @@
+//! Client configuration: how to spawn `codex app-server` and the client's limits.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use super::retry::RetryPolicy;
+use super::stderr::DEFAULT_STDERR_TAIL_BYTES;
+
+/// Default timeout for JSON-RPC requests (60 seconds).
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default maximum accumulated agent text size per turn (16 MB).
+pub const DEFAULT_MAX_AGENT_TEXT_BYTES: usize = 16 * 1024 * 1024;
+
+/// Default maximum bytes per line read from the transport (32 MB).
+/// Lines exceeding this are truncated to prevent OOM from malformed output.
+pub const DEFAULT_MAX_LINE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Environment variable overriding the codex binary path.
+pub const CODEX_BIN_ENV: &str = "CODEX_BIN";
+
+/// Configuration for [`CodexAppServerClient`](super::CodexAppServerClient).
+///
+/// Built with chained setters:
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use codex_appserver::appserver::ClientConfig;
+/// let config = ClientConfig::from_env()
+///     .config_override("model_reasoning_effort", "high")
+///     .cwd("/path/to/project")
+///     .request_timeout(Duration::from_secs(120));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub(crate) codex_bin: PathBuf,
+    pub(crate) args: Vec<String>,
+    pub(crate) config_overrides: Vec<(String, String)>,
+    pub(crate) cwd: Option<PathBuf>,
+    pub(crate) env_allowlist: Option<Vec<String>>,
+    pub(crate) request_timeout: Duration,
+    pub(crate) max_agent_text_bytes: usize,
+    pub(crate) max_line_bytes: usize,
+    pub(crate) stderr_log: Option<PathBuf>,
+    pub(crate) echo_stderr: bool,
+    pub(crate) stderr_tail_bytes: usize,
+    pub(crate) transcript: Option<PathBuf>,
+    pub(crate) retry: RetryPolicy,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            codex_bin: PathBuf::from("codex"),
+            args: Vec::new(),
+            config_overrides: Vec::new(),
+            cwd: None,
+            env_allowlist: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_agent_text_bytes: DEFAULT_MAX_AGENT_TEXT_BYTES,
+            max_line_bytes: DEFAULT_MAX_LINE_BYTES,
+            stderr_log: None,
+            echo_stderr: true,
+            stderr_tail_bytes: DEFAULT_STDERR_TAIL_BYTES,
+            transcript: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Defaults: `codex` from `PATH`, inherited environment and working directory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defaults, with the binary path taken from `CODEX_BIN` when set.
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let mut config = Self::default();
+        if let Some(bin) = lookup(CODEX_BIN_ENV).filter(|b| !b.is_empty()) {
+            config.codex_bin = PathBuf::from(bin);
+        }
+        config
+    }
+
+    /// Path (or `PATH`-resolved name) of the codex binary.
+    pub fn codex_bin(mut self, bin: impl Into<PathBuf>) -> Self {
+        self.codex_bin = bin.into();
+        self
+    }
+
+    /// Append an extra argument passed after `app-server`.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Append a `-c key=value` codex config override.
+    pub fn config_override(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config_overrides.push((key.into(), value.into()));
+        self
+    }
+
+    /// Working directory for the app-server process.
+    pub fn cwd(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    /// Pass only these environment variables through to the app server
+    /// (variables not set in this process are skipped). Default: inherit everything.
+    pub fn env_allowlist<I, S>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.env_allowlist = Some(vars.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Timeout used by [`request`](super::CodexAppServerClient::request).
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Maximum agent text kept per turn; further deltas are dropped.
+    pub fn max_agent_text_bytes(mut self, bytes: usize) -> Self {
+        self.max_agent_text_bytes = bytes;
+        self
+    }
+
+    /// Maximum length of a single JSONL line; longer lines are truncated.
+    pub fn max_line_bytes(mut self, bytes: usize) -> Self {
+        self.max_line_bytes = bytes;
+        self
+    }
+
+    /// Append the server's stderr to this file (parent directories are created).
+    pub fn stderr_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.stderr_log = Some(path.into());
+        self
+    }
+
+    /// Copy the server's stderr to ours as it arrives. Default: `true`.
+    pub fn echo_stderr(mut self, echo: bool) -> Self {
+        self.echo_stderr = echo;
+        self
+    }
+
+    /// Size of the ring buffer keeping the server's most recent stderr lines.
+    pub fn stderr_tail_bytes(mut self, bytes: usize) -> Self {
+        self.stderr_tail_bytes = bytes;
+        self
+    }
+
+    /// Record every line sent to and received from the server in a transcript
+    /// file (appended), for later replay with [`ReplayTransport`](super::ReplayTransport).
+    pub fn transcript(mut self, path: impl Into<PathBuf>) -> Self {
+        self.transcript = Some(path.into());
+        self
+    }
+
+    /// Retry policy for idempotent requests (`initialize`, `thread/start`,
+    /// `model/list`). Default: [`RetryPolicy::default`]; use [`RetryPolicy::none`]
+    /// to fail on the first error.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Build the command line: `<bin> [-c key=value]... app-server [args]...`.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.codex_bin);
+        for (key, value) in &self.config_overrides {
+            command.arg("-c").arg(format!("{key}={value}"));
+        }
+        command.arg("app-server").args(&self.args);
+        if let Some(dir) = &self.cwd {
+            command.current_dir(dir);
+        }
+        if let Some(allowlist) = &self.env_allowlist {
+            command.env_clear();
+            for var in allowlist {
+                if let Some(value) = std::env::var_os(var) {
+                    command.env(var, value);
+                }
+            }
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn command_args(command: &Command) -> Vec<OsString> {
+        command
+            .as_std()
+            .get_args()
+            .map(|a| a.to_os_string())
+            .collect()
+    }
+
+    #[test]
+    fn default_command_is_codex_app_server() {
+        let command = ClientConfig::new().command();
+        assert_eq!(command.as_std().get_program(), "codex");
+        assert_eq!(command_args(&command), vec![OsString::from("app-server")]);
+    }
+
+    #[test]
+    fn overrides_precede_subcommand_and_args_follow() {
+        let config = ClientConfig::new()
+            .config_override("model_reasoning_effort", "high")
+            .arg("--listen")
+            .arg("stdio");
+        assert_eq!(
+            command_args(&config.command()),
+            [
+                "-c",
+                "model_reasoning_effort=high",
+                "app-server",
+                "--listen",
+                "stdio"
+            ]
+            .map(OsString::from)
+            .to_vec()
+        );
+    }
+
+    #[test]
+    fn codex_bin_env_override() {
+        let config = ClientConfig::from_lookup(|key| {
+            (key == CODEX_BIN_ENV).then(|| "/opt/codex/bin/codex".to_string())
+        });
+        assert_eq!(config.codex_bin, PathBuf::from("/opt/codex/bin/codex"));
+
+        let config = ClientConfig::from_lookup(|_| Some(String::new()));
+        assert_eq!(config.codex_bin, PathBuf::from("codex"));
+    }
+
+    #[test]
+    fn explicit_codex_bin_wins_over_env() {
+        let config =
+            ClientConfig::from_lookup(|_| Some("/from/env".to_string())).codex_bin("/explicit");
+        assert_eq!(config.codex_bin, PathBuf::from("/explicit"));
+    }
+
+    #[test]
+    fn env_allowlist_clears_and_copies_present_vars() {
+        let command = ClientConfig::new()
+            .env_allowlist(["PATH", "CODEX_APPSERVER_SURELY_UNSET_VAR"])
+            .command();
+        let envs: Vec<_> = command.as_std().get_envs().collect();
+        assert!(envs.iter().any(|(k, v)| *k == "PATH" && v.is_some()));
+        assert!(!envs
+            .iter()
+            .any(|(k, _)| *k == "CODEX_APPSERVER_SURELY_UNSET_VAR"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Error type shared by the app-server client, transports and protocol parsing.
+
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use super::protocol::JsonRpcError;
+use super::version::Version;
+
+/// JSON-RPC code the app server uses when it is overloaded and sheds a request.
+pub const BACKPRESSURE_ERROR_CODE: i64 = -32001;
+
+/// Errors produced while talking to `codex app-server`.
+#[derive(Debug)]
+pub enum AppServerError {
+    /// The server answered a request with a JSON-RPC error object.
+    Rpc { method: String, error: JsonRpcError },
+    /// No response (or notification) arrived within the allotted time.
+    /// `stderr_tail` holds the server's last stderr lines, when captured.
+    Timeout {
+        operation: String,
+        after: Duration,
+        stderr_tail: Option<String>,
+    },
+    /// The server closed the connection. `status` is known only for child processes
+    /// that had already exited when the error was observed.
+    ServerExited {
+        status: Option<ExitStatus>,
+        stderr_tail: Option<String>,
+    },
+    /// Failed to spawn the app-server process.
+    Spawn(std::io::Error),
+    /// Transport-level I/O failure.
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    /// A message or local JSON file could not be (de)serialized.
+    Json {
+        context: String,
+        source: serde_json::Error,
+    },
+    /// The server sent something that does not fit the protocol.
+    Protocol(String),
+    /// The server is older than the oldest codex this crate supports.
+    UnsupportedServer { version: Version, minimum: Version },
+    /// The server went quiet for `idle` while `operation` was in progress; see
+    /// [`StallWatchdog`](super::watchdog::StallWatchdog).
+    Stalled {
+        operation: String,
+        idle: Duration,
+        stderr_tail: Option<String>,
+    },
+    /// A request kept failing with retryable errors; `last` is the final one.
+    RetriesExhausted {
+        attempts: u32,
+        last: Box<AppServerError>,
+    },
+}
+
+impl AppServerError {
+    pub(crate) fn io(context: impl Into<String>, source: std::io::Error) -> Self {
+        AppServerError::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn json(context: impl Into<String>, source: serde_json::Error) -> Self {
+        AppServerError::Json {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// JSON-RPC error code, if this is a server-reported error.
+    pub fn rpc_code(&self) -> Option<i64> {
+        match self {
+            AppServerError::Rpc { error, .. } => Some(error.code),
+            AppServerError::RetriesExhausted { last, .. } => last.rpc_code(),
+            _ => None,
+        }
+    }
+
+    /// The underlying error, looking through [`AppServerError::RetriesExhausted`].
+    pub fn root(&self) -> &AppServerError {
+        match self {
+            AppServerError::RetriesExhausted { last, .. } => last.root(),
+            other => other,
+        }
+    }
+
+    /// Whether the server rejected the request because it was overloaded.
+    pub fn is_backpressure(&self) -> bool {
+        self.rpc_code() == Some(BACKPRESSURE_ERROR_CODE)
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, AppServerError::Timeout { .. })
+    }
+
+    pub fn is_stalled(&self) -> bool {
+        matches!(self, AppServerError::Stalled { .. })
+    }
+
+    pub fn is_server_exited(&self) -> bool {
+        matches!(self, AppServerError::ServerExited { .. })
+    }
+
+    /// The server's last stderr lines, if they were captured when the error occurred.
+    pub fn stderr_tail(&self) -> Option<&str> {
+        match self {
+            AppServerError::Timeout { stderr_tail, .. }
+            | AppServerError::Stalled { stderr_tail, .. }
+            | AppServerError::ServerExited { stderr_tail, .. } => stderr_tail.as_deref(),
+            AppServerError::RetriesExhausted { last, .. } => last.stderr_tail(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AppServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppServerError::Rpc { method, error } => {
+                write!(f, "Request '{method}' failed: {error}")
+            }
+            AppServerError::Timeout {
+                operation, after, ..
+            } => {
+                write!(
+                    f,
+                    "Timed out after {}s waiting for {operation}",
+                    after.as_secs()
+                )
+            }
+            AppServerError::Stalled {
+                operation, idle, ..
+            } => {
+                write!(
+                    f,
+                    "App server stalled: no messages for {}s during {operation}",
+                    idle.as_secs()
+                )
+            }
+            AppServerError::ServerExited {
+                status: Some(status),
+                ..
+            } => {
+                write!(f, "App server exited ({status})")
+            }
+            AppServerError::ServerExited { status: None, .. } => write!(f, "App server exited"),
+            AppServerError::Spawn(e) => write!(f, "Failed to spawn codex app-server: {e}"),
+            AppServerError::Io { context, source } => write!(f, "{context}: {source}"),
+            AppServerError::Json { context, source } => write!(f, "{context}: {source}"),
+            AppServerError::Protocol(msg) => write!(f, "Protocol error: {msg}"),
+            AppServerError::UnsupportedServer { version, minimum } => write!(
+                f,
+                "codex {version} is not supported (need {minimum} or newer); upgrade codex"
+            ),
+            // `last` prints its own stderr tail.
+            AppServerError::RetriesExhausted { attempts, last } => {
+                return write!(f, "Gave up after {attempts} attempts: {last}");
+            }
+        }?;
+        if let Some(tail) = self.stderr_tail() {
+            write!(f, "\n--- app-server stderr (last lines) ---\n{tail}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AppServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppServerError::Spawn(e) => Some(e),
+            AppServerError::Io { source, .. } => Some(source),
+            AppServerError::Json { source, .. } => Some(source),
+            AppServerError::RetriesExhausted { last, .. } => Some(last.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppServerError {
+    fn from(e: serde_json::Error) -> Self {
+        AppServerError::json("Invalid JSON", e)
+    }
+}
@@ -0,0 +1,353 @@
+//! Typed server events decoded from app-server notifications.
+//!
+//! The reader task decodes every notification into a [`ServerEvent`] and fans it
+//! out to subscribers (see [`CodexAppServerClient::subscribe`]). Methods the client
+//! does not model yet are forwarded as [`ServerEvent::Other`].
+//!
+//! [`CodexAppServerClient::subscribe`]: super::CodexAppServerClient::subscribe
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+/// Events buffered per subscriber before it starts lagging (oldest are dropped).
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Token counts for one model call or a running total.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_output_tokens: u64,
+    pub total_tokens: u64,
+}
+
+/// Payload of `thread/tokenUsage/updated`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThreadTokenUsage {
+    /// Running total for the thread.
+    pub total: TokenUsage,
+    /// Usage of the most recent model call.
+    pub last: TokenUsage,
+    pub model_context_window: Option<u64>,
+}
+
+/// A typed notification from the app server.
+#[derive(Debug, Clone)]
+pub enum ServerEvent {
+    TurnStarted {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+    },
+    /// `turn` is the raw turn object (`id`, `status`, optional `error`).
+    TurnCompleted {
+        thread_id: Option<String>,
+        turn: Value,
+    },
+    /// `item` is the raw thread item (`id`, `type`, and type-specific fields).
+    ItemStarted {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        item: Value,
+    },
+    ItemCompleted {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        item: Value,
+    },
+    AgentMessageDelta {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        item_id: Option<String>,
+        delta: String,
+    },
+    /// Reasoning text; `summary` distinguishes summary deltas from raw reasoning.
+    ReasoningDelta {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        item_id: Option<String>,
+        delta: String,
+        summary: bool,
+    },
+    CommandOutputDelta {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        item_id: Option<String>,
+        delta: String,
+    },
+    TokenUsage {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        usage: ThreadTokenUsage,
+    },
+    Error {
+        thread_id: Option<String>,
+        turn_id: Option<String>,
+        message: String,
+        will_retry: bool,
+    },
+    /// Any notification not modelled above.
+    Other { method: String, params: Value },
+}
+
+impl ServerEvent {
+    /// Decode a notification. Never fails: unknown or malformed payloads become `Other`.
+    pub fn from_notification(method: &str, params: &Value) -> Self {
+        let thread_id = str_field(params, "threadId");
+        let turn_id = str_field(params, "turnId");
+        let item_id = str_field(params, "itemId");
+        let delta = || {
+            params
+                .get("delta")
+                .and_then(|d| d.as_str())
+                .map(|s| s.to_string())
+        };
+
+        match method {
+            "turn/started" => ServerEvent::TurnStarted {
+                thread_id,
+                turn_id: turn_id.or_else(|| {
+                    params
+                        .get("turn")
+                        .and_then(|t| t.get("id"))
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string())
+                }),
+            },
+            "turn/completed" => ServerEvent::TurnCompleted {
+                thread_id,
+                turn: params.get("turn").cloned().unwrap_or(Value::Null),
+            },
+            "item/started" | "item/completed" => {
+                let item = params
+                    .get("item")
+                    .cloned()
+                    .unwrap_or_else(|| params.clone());
+                if method == "item/started" {
+                    ServerEvent::ItemStarted {
+                        thread_id,
+                        turn_id,
+                        item,
+                    }
+                } else {
+                    ServerEvent::ItemCompleted {
+                        thread_id,
+                        turn_id,
+                        item,
+                    }
+                }
+            }
+            "item/agentMessage/delta" => match delta() {
+                Some(delta) => ServerEvent::AgentMessageDelta {
+                    thread_id,
+                    turn_id,
+                    item_id,
+                    delta,
+                },
+                None => Self::other(method, params),
+            },
+            "item/reasoning/textDelta" | "item/reasoning/summaryTextDelta" => match delta() {
+                Some(delta) => ServerEvent::ReasoningDelta {
+                    thread_id,
+                    turn_id,
+                    item_id,
+                    delta,
+                    summary: method == "item/reasoning/summaryTextDelta",
+                },
+                None => Self::other(method, params),
+            },
+            "item/commandExecution/outputDelta" => match delta() {
+                Some(delta) => ServerEvent::CommandOutputDelta {
+                    thread_id,
+                    turn_id,
+                    item_id,
+                    delta,
+                },
+                None => Self::other(method, params),
+            },
+            "thread/tokenUsage/updated" => {
+                match params
+                    .get("tokenUsage")
+                    .map(|u| serde_json::from_value::<ThreadTokenUsage>(u.clone()))
+                {
+                    Some(Ok(usage)) => ServerEvent::TokenUsage {
+                        thread_id,
+                        turn_id,
+                        usage,
+                    },
+                    _ => Self::other(method, params),
+                }
+            }
+            "error" => {
+                let error = params.get("error").unwrap_or(params);
+                let message = error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| error.to_string());
+                ServerEvent::Error {
+                    thread_id,
+                    turn_id,
+                    message,
+                    will_retry: params
+                        .get("willRetry")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false),
+                }
+            }
+            _ => Self::other(method, params),
+        }
+    }
+
+    fn other(method: &str, params: &Value) -> Self {
+        ServerEvent::Other {
+            method: method.to_string(),
+            params: params.clone(),
+        }
+    }
+}
+
+fn str_field(params: &Value, key: &str) -> Option<String> {
+    params
+        .get(key)
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// A subscription to server events. Ends when the connection closes.
+pub struct EventStream {
+    rx: Option<broadcast::Receiver<ServerEvent>>,
+}
+
+impl EventStream {
+    pub(crate) fn new(rx: Option<broadcast::Receiver<ServerEvent>>) -> Self {
+        Self { rx }
+    }
+
+    /// Receive the next event, or `None` once the server connection is gone.
+    /// A subscriber that falls more than [`EVENT_CHANNEL_CAPACITY`] events behind
+    /// skips the oldest ones (logged) rather than stalling the reader.
+    pub async fn next(&mut self) -> Option<ServerEvent> {
+        let rx = self.rx.as_mut()?;
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    eprintln!("[appserver-events] subscriber lagged, skipped {skipped} events");
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    self.rx = None;
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// The next event if one is already buffered, without waiting. Use it to drain
+    /// events that arrived before a turn's completion was observed.
+    pub fn try_next(&mut self) -> Option<ServerEvent> {
+        let rx = self.rx.as_mut()?;
+        loop {
+            match rx.try_recv() {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                    eprintln!("[appserver-events] subscriber lagged, skipped {skipped} events");
+                }
+                Err(broadcast::error::TryRecvError::Empty) => return None,
+                Err(broadcast::error::TryRecvError::Closed) => {
+                    self.rx = None;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_agent_message_delta_with_ids() {
+        let event = ServerEvent::from_notification(
+            "item/agentMessage/delta",
+            &json!({"threadId":"thr_1","turnId":"turn_1","itemId":"item_1","delta":"hi"}),
+        );
+        match event {
+            ServerEvent::AgentMessageDelta {
+                thread_id,
+                turn_id,
+                item_id,
+                delta,
+            } => {
+                assert_eq!(thread_id.as_deref(), Some("thr_1"));
+                assert_eq!(turn_id.as_deref(), Some("turn_1"));
+                assert_eq!(item_id.as_deref(), Some("item_1"));
+                assert_eq!(delta, "hi");
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn decodes_token_usage() {
+        let event = ServerEvent::from_notification(
+            "thread/tokenUsage/updated",
+            &json!({"threadId":"thr_1","turnId":"turn_1","tokenUsage":{
+                "total":{"inputTokens":100,"cachedInputTokens":40,"outputTokens":20,"reasoningOutputTokens":5,"totalTokens":120},
+                "last":{"inputTokens":10,"outputTokens":2,"totalTokens":12},
+                "modelContextWindow":272000
+            }}),
+        );
+        match event {
+            ServerEvent::TokenUsage { usage, .. } => {
+                assert_eq!(usage.total.input_tokens, 100);
+                assert_eq!(usage.total.cached_input_tokens, 40);
+                assert_eq!(usage.last.cached_input_tokens, 0);
+                assert_eq!(usage.model_context_window, Some(272000));
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn decodes_error_notification() {
+        let event = ServerEvent::from_notification(
+            "error",
+            &json!({"error":{"message":"stream disconnected"},"willRetry":true}),
+        );
+        match event {
+            ServerEvent::Error {
+                message,
+                will_retry,
+                ..
+            } => {
+                assert_eq!(message, "stream disconnected");
+                assert!(will_retry);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn delta_without_text_falls_back_to_other() {
+        let event = ServerEvent::from_notification("item/agentMessage/delta", &json!({}));
+        assert!(matches!(event, ServerEvent::Other { .. }));
+    }
+
+    #[test]
+    fn unknown_method_is_other() {
+        let event = ServerEvent::from_notification("mcp/startup", &json!({"x":1}));
+        match event {
+            ServerEvent::Other { method, params } => {
+                assert_eq!(method, "mcp/startup");
+                assert_eq!(params["x"], 1);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
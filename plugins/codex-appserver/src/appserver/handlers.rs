@@ -0,0 +1,121 @@
+//! Handlers for server-initiated JSON-RPC requests.
+//!
+//! The app server asks the client for decisions mid-turn (command execution and
+//! patch approvals). Every such request must be answered or the turn stalls, so
+//! unknown methods are rejected with "method not found" rather than ignored.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use super::protocol::JsonRpcError;
+
+/// Callback answering a server request: receives `params`, returns the `result`
+/// to send back or a JSON-RPC error.
+pub type RequestHandler = Arc<dyn Fn(&Value) -> Result<Value, JsonRpcError> + Send + Sync>;
+
+/// Approval request methods (v2 item-based API) answered with `"decline"`.
+pub const ITEM_APPROVAL_METHODS: &[&str] = &[
+    "item/commandExecution/requestApproval",
+    "item/fileChange/requestApproval",
+];
+
+/// Approval request methods (legacy API) answered with `"denied"`.
+pub const LEGACY_APPROVAL_METHODS: &[&str] = &["execCommandApproval", "applyPatchApproval"];
+
+/// Registry of server-request handlers, keyed by method name.
+#[derive(Clone)]
+pub struct RequestHandlers {
+    handlers: HashMap<String, RequestHandler>,
+}
+
+impl RequestHandlers {
+    /// Empty registry: every server request is answered with "method not found".
+    pub fn empty() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registry with the default policy: decline every approval request.
+    /// Reviews run in a read-only sandbox, so nothing should ever be approved.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::empty();
+        for method in ITEM_APPROVAL_METHODS {
+            registry.insert(*method, Arc::new(|_| Ok(json!({ "decision": "decline" }))));
+        }
+        for method in LEGACY_APPROVAL_METHODS {
+            registry.insert(*method, Arc::new(|_| Ok(json!({ "decision": "denied" }))));
+        }
+        registry
+    }
+
+    /// Register (or replace) the handler for `method`.
+    pub fn insert(&mut self, method: impl Into<String>, handler: RequestHandler) {
+        self.handlers.insert(method.into(), handler);
+    }
+
+    /// Remove the handler for `method`, returning it if present.
+    pub fn remove(&mut self, method: &str) -> Option<RequestHandler> {
+        self.handlers.remove(method)
+    }
+
+    /// Answer a server request.
+    pub fn dispatch(&self, method: &str, params: &Value) -> Result<Value, JsonRpcError> {
+        match self.handlers.get(method) {
+            Some(handler) => handler(params),
+            None => Err(JsonRpcError::method_not_found(method)),
+        }
+    }
+}
+
+impl Default for RequestHandlers {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_decline_item_approvals() {
+        let handlers = RequestHandlers::with_defaults();
+        for method in ITEM_APPROVAL_METHODS {
+            let result = handlers.dispatch(method, &json!({})).unwrap();
+            assert_eq!(result["decision"], "decline");
+        }
+    }
+
+    #[test]
+    fn defaults_deny_legacy_approvals() {
+        let handlers = RequestHandlers::with_defaults();
+        for method in LEGACY_APPROVAL_METHODS {
+            let result = handlers.dispatch(method, &json!({})).unwrap();
+            assert_eq!(result["decision"], "denied");
+        }
+    }
+
+    #[test]
+    fn unknown_method_is_not_found() {
+        let err = RequestHandlers::with_defaults()
+            .dispatch("tool/call", &Value::Null)
+            .unwrap_err();
+        assert_eq!(err.code, -32601);
+    }
+
+    #[test]
+    fn insert_overrides_default() {
+        let mut handlers = RequestHandlers::with_defaults();
+        handlers.insert(
+            ITEM_APPROVAL_METHODS[0],
+            Arc::new(|_| Ok(json!({ "decision": "accept" }))),
+        );
+        let result = handlers
+            .dispatch(ITEM_APPROVAL_METHODS[0], &json!({}))
+            .unwrap();
+        assert_eq!(result["decision"], "accept");
+    }
+}
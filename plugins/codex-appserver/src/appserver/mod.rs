@@ -1,12 +1,46 @@
 //! Codex App Server client module.
 //!
 //! Provides a JSON-RPC 2.0 client for communicating with `codex app-server`
-//! over stdio, plus protocol types and review output structures.
+//! over stdio (or another transport), plus protocol types and review output structures.
 
 pub mod client;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod handlers;
 pub mod protocol;
+pub mod recorder;
+pub mod report;
+pub mod retry;
+pub mod stderr;
+pub mod supervisor;
+pub mod transcript;
+pub mod transport;
+pub mod usage;
+pub mod version;
+pub mod watchdog;
 
 pub use client::{CodexAppServerClient, ShutdownStatus};
+pub use config::ClientConfig;
+pub use error::{AppServerError, BACKPRESSURE_ERROR_CODE};
+pub use events::{EventStream, ServerEvent, ThreadTokenUsage, TokenUsage};
+pub use handlers::{RequestHandler, RequestHandlers};
 pub use protocol::{
     review_output_schema, Dimension, Finding, JsonRpcError, ReviewOutput, Severity,
 };
+pub use recorder::{read_transcript, Direction, ReplayTransport, TranscriptRecord};
+pub use report::{ReportFormat, ReportOptions};
+pub use retry::RetryPolicy;
+pub use stderr::StderrBuffer;
+pub use supervisor::SupervisedClient;
+pub use transcript::{TranscriptItem, TurnTranscript};
+#[cfg(unix)]
+pub use transport::UnixSocketTransport;
+pub use transport::{
+    ChildStdioTransport, DuplexTransport, TcpTransport, Transport, TransportParts,
+};
+pub use usage::{
+    append_usage_record, read_usage_ledger, ModelPrice, PriceTable, TurnUsage, UsageRecord,
+};
+pub use version::{ServerFeatures, Version, MIN_CODEX_VERSION, OUTPUT_SCHEMA_MIN_VERSION};
+pub use watchdog::{StallCheck, StallWatchdog};
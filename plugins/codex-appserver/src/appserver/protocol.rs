@@ -3,6 +3,38 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::error::AppServerError;
+
+/// JSON-RPC 2.0 request id. Our own requests use numbers, but the server may use
+/// strings for the requests it sends us; replies echo the id back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum RequestId {
+    Number(u64),
+    String(String),
+}
+
+impl From<u64> for RequestId {
+    fn from(id: u64) -> Self {
+        Self::Number(id)
+    }
+}
+
+impl From<&str> for RequestId {
+    fn from(id: &str) -> Self {
+        Self::String(id.to_string())
+    }
+}
+
+impl std::fmt::Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(id) => write!(f, "{id}"),
+            Self::String(id) => write!(f, "{id:?}"),
+        }
+    }
+}
+
 /// JSON-RPC 2.0 request (client → server).
 #[derive(Debug, Serialize)]
 pub struct JsonRpcRequest {
@@ -46,36 +78,83 @@ impl JsonRpcNotification {
 /// JSON-RPC 2.0 response (server → client).
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcResponse {
-    pub id: Option<u64>,
+    pub id: Option<RequestId>,
     pub result: Option<Value>,
     pub error: Option<JsonRpcError>,
 }
 
+/// JSON-RPC 2.0 response (client → server), answering a server-initiated request.
+#[derive(Debug, Serialize)]
+pub struct JsonRpcReply {
+    pub jsonrpc: &'static str,
+    pub id: RequestId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcReply {
+    pub fn new(id: impl Into<RequestId>, outcome: Result<Value, JsonRpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(value) => (Some(value), None),
+            Err(err) => (None, Some(err)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id: id.into(),
+            result,
+            error,
+        }
+    }
+}
+
 /// JSON-RPC 2.0 error object.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct JsonRpcError {
     pub code: i64,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Value>,
 }
 
+impl JsonRpcError {
+    /// Standard JSON-RPC "method not found" error (-32601).
+    pub fn method_not_found(method: &str) -> Self {
+        Self {
+            code: -32601,
+            message: format!("Method not found: {method}"),
+            data: None,
+        }
+    }
+}
+
 impl std::fmt::Display for JsonRpcError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "JSON-RPC error {}: {}", self.code, self.message)
     }
 }
 
-/// A message received from the server (response or notification).
+/// A message received from the server (response, request or notification).
 #[derive(Debug)]
 pub enum ServerMessage {
     Response(JsonRpcResponse),
-    Notification { method: String, params: Value },
+    /// Server-initiated request that expects a reply (e.g. approval prompts).
+    Request {
+        id: RequestId,
+        method: String,
+        params: Value,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
 }
 
 /// Raw server-side JSON-RPC message (for deserialization).
 #[derive(Debug, Deserialize)]
 struct RawServerMessage {
-    id: Option<u64>,
+    id: Option<RequestId>,
     method: Option<String>,
     result: Option<Value>,
     error: Option<JsonRpcError>,
@@ -85,29 +164,254 @@ struct RawServerMessage {
 
 impl ServerMessage {
     /// Parse a JSON line from the server into a typed message.
-    pub fn parse(line: &str) -> Result<Self, String> {
-        let raw: RawServerMessage =
-            serde_json::from_str(line).map_err(|e| format!("Invalid JSON: {e}"))?;
-
-        if raw.id.is_some() {
-            // Response (has id)
-            Ok(ServerMessage::Response(JsonRpcResponse {
-                id: raw.id,
+    pub fn parse(line: &str) -> Result<Self, AppServerError> {
+        let raw: RawServerMessage = serde_json::from_str(line)?;
+
+        match (raw.id, raw.method) {
+            // Request (has id and method)
+            (Some(id), Some(method)) => Ok(ServerMessage::Request {
+                id,
+                method,
+                params: raw.params,
+            }),
+            // Response (has id, no method)
+            (Some(id), None) => Ok(ServerMessage::Response(JsonRpcResponse {
+                id: Some(id),
                 result: raw.result,
                 error: raw.error,
-            }))
-        } else if let Some(method) = raw.method {
+            })),
             // Notification (has method, no id)
-            Ok(ServerMessage::Notification {
+            (None, Some(method)) => Ok(ServerMessage::Notification {
                 method,
                 params: raw.params,
-            })
-        } else {
-            Err("Message has neither id nor method".to_string())
+            }),
+            (None, None) => Err(AppServerError::Protocol(
+                "Message has neither id nor method".to_string(),
+            )),
+        }
+    }
+}
+
+// --- App-server method types (params and results of the methods used here) ---
+
+/// Identifies the client in `initialize`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ClientInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Params of `initialize`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitializeParams {
+    pub client_info: ClientInfo,
+    #[serde(default)]
+    pub capabilities: serde_json::Map<String, Value>,
+}
+
+impl InitializeParams {
+    /// Params identifying the client by `name` and `version`, with no capabilities.
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            client_info: ClientInfo {
+                name: name.into(),
+                version: version.into(),
+                title: None,
+            },
+            capabilities: serde_json::Map::new(),
+        }
+    }
+}
+
+/// Result of `initialize`. Current servers only send `userAgent`; the other
+/// fields are read when present. See [`InitializeResponse::server_version`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InitializeResponse {
+    pub user_agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_info: Option<ServerInfo>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub capabilities: serde_json::Map<String, Value>,
+}
+
+/// Identifies the server in the `initialize` result.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+}
+
+/// Sandbox applied to commands the agent runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SandboxMode {
+    ReadOnly,
+    WorkspaceWrite,
+    DangerFullAccess,
+}
+
+/// When the server asks the client to approve a command or patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApprovalPolicy {
+    Untrusted,
+    OnFailure,
+    OnRequest,
+    Never,
+}
+
+/// Params of `thread/start`; also the settings reapplied by `thread/resume`.
+/// Unset fields fall back to the server's configuration.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadStartParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_policy: Option<ApprovalPolicy>,
+}
+
+/// Params of `thread/resume`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadResumeParams {
+    pub thread_id: String,
+    #[serde(flatten)]
+    pub settings: ThreadStartParams,
+}
+
+/// A conversation thread.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Thread {
+    pub id: String,
+}
+
+/// Result of `thread/start` and `thread/resume`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ThreadStartResponse {
+    pub thread: Thread,
+}
+
+/// One piece of user input to a turn.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UserInput {
+    Text { text: String },
+}
+
+/// Params of `turn/start`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnStartParams {
+    pub thread_id: String,
+    pub input: Vec<UserInput>,
+    /// JSON Schema the final agent message must conform to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>,
+}
+
+impl TurnStartParams {
+    /// A turn with a single text input.
+    pub fn text(thread_id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            input: vec![UserInput::Text { text: text.into() }],
+            output_schema: None,
         }
     }
 }
 
+/// Lifecycle state of a turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TurnStatus {
+    InProgress,
+    Completed,
+    Interrupted,
+    Failed,
+    /// A status this client does not know yet.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Why a turn failed.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TurnError {
+    #[serde(default)]
+    pub message: String,
+}
+
+/// A turn, as reported by `turn/start` and `turn/completed`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Turn {
+    pub id: String,
+    pub status: TurnStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<TurnError>,
+}
+
+/// Result of `turn/start`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TurnStartResponse {
+    pub turn: Turn,
+}
+
+/// Params of the `turn/completed` notification.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnCompletedParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    pub turn: Turn,
+}
+
+/// Params of `turn/interrupt`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnInterruptParams {
+    pub thread_id: String,
+    pub turn_id: String,
+}
+
+/// Params of `model/list`. Both fields are optional; the default lists the first page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelListParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// A model the server can run.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Model {
+    pub id: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// Result of `model/list`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelListResponse {
+    pub data: Vec<Model>,
+    pub next_cursor: Option<String>,
+}
+
 // --- Review output types (structured output from codex) ---
 
 /// Structured review output matching the outputSchema.
@@ -153,6 +457,34 @@ impl std::fmt::Display for Severity {
     }
 }
 
+impl Severity {
+    /// Higher is more severe: `Low` is 0, `Critical` 3.
+    pub fn rank(self) -> u8 {
+        match self {
+            Severity::Critical => 3,
+            Severity::High => 2,
+            Severity::Medium => 1,
+            Severity::Low => 0,
+        }
+    }
+
+    /// Whether this is at least as severe as `threshold`.
+    pub fn at_least(self, threshold: Severity) -> bool {
+        self.rank() >= threshold.rank()
+    }
+
+    /// Parse a severity name, ignoring case (`high`, `HIGH`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "CRITICAL" => Some(Severity::Critical),
+            "HIGH" => Some(Severity::High),
+            "MEDIUM" => Some(Severity::Medium),
+            "LOW" => Some(Severity::Low),
+            _ => None,
+        }
+    }
+}
+
 /// Review dimension category.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Dimension {
@@ -0,0 +1,354 @@
+//! Protocol transcripts: recording the JSONL exchanged with the app server and
+//! replaying it offline.
+//!
+//! A transcript is itself JSONL, one [`TranscriptRecord`] per line:
+//!
+//! ```text
+//! {"ts_ms":1767225600123,"dir":"send","line":"{\"jsonrpc\":\"2.0\",\"id\":1,...}"}
+//! {"ts_ms":1767225600456,"dir":"recv","line":"{\"id\":1,\"result\":{...}}"}
+//! ```
+//!
+//! Recording is enabled with [`ClientConfig::transcript`](super::ClientConfig::transcript).
+//! [`ReplayTransport`] plays the `recv` lines back to a client in order, waiting for
+//! the client's own line wherever the transcript has a `send`, so a recorded review
+//! can be re-run without a live server and tests can use recorded "cassettes".
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, oneshot};
+
+use super::error::AppServerError;
+use super::transport::{Transport, TransportParts};
+
+/// Direction of a transcript line, from the client's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Written by the client.
+    Send,
+    /// Read from the server.
+    Recv,
+}
+
+/// One line of a protocol transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptRecord {
+    /// Milliseconds since the Unix epoch when the line was written or read.
+    pub ts_ms: u64,
+    pub dir: Direction,
+    /// The JSONL line exactly as exchanged, without the trailing newline.
+    pub line: String,
+}
+
+/// Appends transcript records to a file. Shared by the client's reader and writer.
+///
+/// Records are handed to a dedicated thread that does the file I/O, so a slow disk
+/// never holds up the async tasks carrying JSON-RPC traffic.
+pub(crate) struct Recorder {
+    records: mpsc::UnboundedSender<RecorderCommand>,
+}
+
+enum RecorderCommand {
+    Record(TranscriptRecord),
+    /// Called once every earlier record has been written.
+    Flush(Box<dyn FnOnce() + Send>),
+}
+
+impl Recorder {
+    /// Open `path` for appending, creating parent directories.
+    pub(crate) fn create(path: &Path) -> Result<Self, AppServerError> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                AppServerError::io(format!("Failed to create {}", parent.display()), e)
+            })?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| AppServerError::io(format!("Failed to open {}", path.display()), e))?;
+        let (records, commands) = mpsc::unbounded_channel();
+        let display = path.display().to_string();
+        std::thread::Builder::new()
+            .name("appserver-recorder".to_string())
+            .spawn(move || write_records(file, commands, &display))
+            .map_err(|e| AppServerError::io("Failed to start the transcript writer", e))?;
+        Ok(Self { records })
+    }
+
+    /// Queue one record; it is stamped now and written in order.
+    pub(crate) fn record(&self, dir: Direction, line: &str) {
+        let record = TranscriptRecord {
+            ts_ms: now_ms(),
+            dir,
+            line: line.to_string(),
+        };
+        let _ = self.records.send(RecorderCommand::Record(record));
+    }
+
+    /// Wait until every record queued so far is in the file.
+    pub(crate) async fn flush(&self) {
+        let (ack, ack_rx) = oneshot::channel();
+        let ack = Box::new(move || {
+            let _ = ack.send(());
+        });
+        if self.records.send(RecorderCommand::Flush(ack)).is_ok() {
+            let _ = ack_rx.await;
+        }
+    }
+
+    /// [`flush`](Self::flush) for teardown, where nothing can be awaited: blocks
+    /// for at most `timeout`.
+    pub(crate) fn flush_blocking(&self, timeout: std::time::Duration) {
+        let (ack, ack_rx) = std::sync::mpsc::channel();
+        let ack = Box::new(move || {
+            let _ = ack.send(());
+        });
+        if self.records.send(RecorderCommand::Flush(ack)).is_ok() {
+            let _ = ack_rx.recv_timeout(timeout);
+        }
+    }
+}
+
+/// Writer thread: runs until every [`Recorder`] handle is gone. Each record is
+/// written with a single `write_all`, so a crash never leaves a half-written line
+/// behind a complete one. The first failure is reported once; later ones are not.
+fn write_records(
+    mut file: std::fs::File,
+    mut commands: mpsc::UnboundedReceiver<RecorderCommand>,
+    path: &str,
+) {
+    let mut warned = false;
+    while let Some(command) = commands.blocking_recv() {
+        let record = match command {
+            RecorderCommand::Record(record) => record,
+            RecorderCommand::Flush(ack) => {
+                ack();
+                continue;
+            }
+        };
+        let result = serde_json::to_string(&record)
+            .map_err(std::io::Error::from)
+            .and_then(|mut json| {
+                json.push('\n');
+                file.write_all(json.as_bytes())
+            });
+        if let Err(e) = result {
+            if !warned {
+                eprintln!(
+                    "[appserver-recorder] failed to write transcript {path}: {e} \
+                     (further failures are not reported)"
+                );
+                warned = true;
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Read a transcript file. Blank lines are skipped; any other malformed line is an error.
+pub fn read_transcript(path: impl AsRef<Path>) -> Result<Vec<TranscriptRecord>, AppServerError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppServerError::io(format!("Failed to read {}", path.display()), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                AppServerError::json(
+                    format!("Invalid transcript record at {}:{}", path.display(), i + 1),
+                    e,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Transport that replays a recorded transcript instead of talking to a server.
+///
+/// `recv` records are written to the client in order. At each `send` record the
+/// replay waits for the client to write a line, so responses never overtake the
+/// requests they answer. Request ids are remapped, so the client need not number
+/// its requests exactly as the recorded one did. A sent method that differs from
+/// the recording is logged but does not stop the replay. Once the transcript is
+/// exhausted the connection is closed, as if the server had exited.
+pub struct ReplayTransport {
+    records: Vec<TranscriptRecord>,
+}
+
+impl ReplayTransport {
+    pub fn new(records: Vec<TranscriptRecord>) -> Self {
+        Self { records }
+    }
+
+    /// Replay the transcript at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AppServerError> {
+        Ok(Self::new(read_transcript(path)?))
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn into_parts(self) -> TransportParts {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(run_replay(self.records, server));
+        let (reader, writer) = tokio::io::split(client);
+        TransportParts {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: None,
+            stderr: None,
+        }
+    }
+}
+
+/// Server side of a replay: plays `records` against the client on `stream`.
+async fn run_replay(records: Vec<TranscriptRecord>, stream: tokio::io::DuplexStream) {
+    let (read, mut write) = tokio::io::split(stream);
+    let mut lines = BufReader::new(read).lines();
+    // Recorded request id -> id the replaying client used for the same request.
+    let mut ids: HashMap<u64, u64> = HashMap::new();
+
+    for (index, record) in records.iter().enumerate() {
+        match record.dir {
+            Direction::Send => {
+                let Ok(Some(actual)) = lines.next_line().await else {
+                    eprintln!(
+                        "[appserver-replay] client closed the connection at record {}",
+                        index + 1
+                    );
+                    return;
+                };
+                let (Ok(expected), Ok(actual)) = (
+                    serde_json::from_str::<Value>(&record.line),
+                    serde_json::from_str::<Value>(&actual),
+                ) else {
+                    continue;
+                };
+                if expected.get("method") != actual.get("method") {
+                    eprintln!(
+                        "[appserver-replay] divergence at record {}: expected {}, got {}",
+                        index + 1,
+                        expected.get("method").unwrap_or(&Value::Null),
+                        actual.get("method").unwrap_or(&Value::Null)
+                    );
+                }
+                if let (Some(recorded), Some(id)) = (
+                    expected.get("method").and(expected["id"].as_u64()),
+                    actual["id"].as_u64(),
+                ) {
+                    ids.insert(recorded, id);
+                }
+            }
+            Direction::Recv => {
+                let line = remap_response_id(&record.line, &ids);
+                if write.write_all(line.as_bytes()).await.is_err()
+                    || write.write_all(b"\n").await.is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+
+    // Transcript exhausted: signal EOF, but keep draining so late client writes
+    // (e.g. the `exit` notification) do not fail.
+    let _ = write.shutdown().await;
+    drop(write);
+    while let Ok(Some(_)) = lines.next_line().await {}
+}
+
+/// Rewrite the id of a recorded response to the id the replaying client used.
+/// Server-initiated requests and notifications pass through unchanged.
+fn remap_response_id(line: &str, ids: &HashMap<u64, u64>) -> String {
+    let Ok(mut msg) = serde_json::from_str::<Value>(line) else {
+        return line.to_string();
+    };
+    if msg.get("method").is_some() {
+        return line.to_string();
+    }
+    match msg["id"].as_u64().and_then(|id| ids.get(&id)) {
+        Some(&actual) => {
+            msg["id"] = Value::from(actual);
+            msg.to_string()
+        }
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_roundtrips_with_lowercase_direction() {
+        let record = TranscriptRecord {
+            ts_ms: 1,
+            dir: Direction::Recv,
+            line: r#"{"id":1,"result":{}}"#.to_string(),
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains(r#""dir":"recv""#));
+        assert_eq!(
+            serde_json::from_str::<TranscriptRecord>(&json).unwrap(),
+            record
+        );
+    }
+
+    #[test]
+    fn recorder_appends_and_reader_skips_blank_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/session.jsonl");
+        let recorder = Recorder::create(&path).unwrap();
+        recorder.record(Direction::Send, r#"{"method":"initialize","id":1}"#);
+        recorder.record(Direction::Recv, r#"{"id":1,"result":{}}"#);
+        recorder.flush_blocking(std::time::Duration::from_secs(5));
+        drop(recorder);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"\n")
+            .unwrap();
+
+        let records = read_transcript(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].dir, Direction::Send);
+        assert_eq!(records[1].line, r#"{"id":1,"result":{}}"#);
+    }
+
+    #[test]
+    fn malformed_transcript_line_reports_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bad.jsonl");
+        std::fs::write(
+            &path,
+            "{\"ts_ms\":1,\"dir\":\"send\",\"line\":\"\"}\nnot json\n",
+        )
+        .unwrap();
+        let err = read_transcript(&path).unwrap_err();
+        assert!(err.to_string().contains("bad.jsonl:2"), "got: {err}");
+    }
+
+    #[test]
+    fn remaps_response_ids_only() {
+        let ids = HashMap::from([(7, 1)]);
+        let response = remap_response_id(r#"{"jsonrpc":"2.0","id":7,"result":{}}"#, &ids);
+        assert_eq!(serde_json::from_str::<Value>(&response).unwrap()["id"], 1);
+
+        let server_request = r#"{"id":7,"method":"execCommandApproval","params":{}}"#;
+        assert_eq!(remap_response_id(server_request, &ids), server_request);
+    }
+}
@@ -0,0 +1,82 @@
+//! Checkstyle XML output.
+//!
+//! Findings are grouped into one `<file>` element per path, each an `<error>` at
+//! its line. Critical and high findings are errors, medium ones warnings and low
+//! ones info. `source` is `codex-review.<dimension>`, e.g. `codex-review.bugs`.
+
+use super::{escape_xml, group_by_file, rule_id};
+use crate::appserver::protocol::{Finding, ReviewOutput, Severity};
+
+/// Version of the Checkstyle format written.
+pub const CHECKSTYLE_VERSION: &str = "4.3";
+
+/// Render `review` as a Checkstyle XML document.
+pub fn render(review: &ReviewOutput) -> String {
+    let files = group_by_file(review);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<checkstyle version=\"{CHECKSTYLE_VERSION}\">\n"));
+    for (file, findings) in &files {
+        xml.push_str(&format!("  <file name=\"{}\">\n", escape_xml(file)));
+        for finding in findings {
+            let line = finding
+                .line
+                .map(|line| format!(" line=\"{line}\""))
+                .unwrap_or_default();
+            xml.push_str(&format!(
+                "    <error{line} severity=\"{}\" message=\"{}\" source=\"codex-review.{}\"/>\n",
+                severity(finding.severity),
+                escape_xml(&message(finding)),
+                rule_id(finding.dimension)
+            ));
+        }
+        xml.push_str("  </file>\n");
+    }
+    xml.push_str("</checkstyle>\n");
+    xml
+}
+
+/// Checkstyle severity for a finding severity.
+pub fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low => "info",
+    }
+}
+
+fn message(finding: &Finding) -> String {
+    format!(
+        "[{}] {}: {} Suggestion: {}",
+        finding.severity, finding.title, finding.problem, finding.suggestion
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::protocol::Dimension;
+    use crate::appserver::report::test_support::{finding, review};
+
+    #[test]
+    fn errors_are_grouped_by_file_with_severity_mapping() {
+        let long = |file: &str, severity| {
+            finding("Long \"function\"")
+                .dimension(Dimension::CodeQuality)
+                .file(file)
+                .severity(severity)
+        };
+        let xml = render(&review([
+            long("src/a.rs", Severity::Critical).line(10),
+            long("src/b.rs", Severity::Low).no_line(),
+            long("src\\a.rs", Severity::Medium).line(20),
+        ]));
+        assert_eq!(xml.matches("<file ").count(), 2);
+        assert!(xml.contains(
+            "<error line=\"10\" severity=\"error\" message=\"[CRITICAL] Long &quot;function&quot;: \
+             May panic. Suggestion: Use get().\" source=\"codex-review.code-quality\"/>"
+        ));
+        assert!(xml.contains("<error line=\"20\" severity=\"warning\""));
+        assert!(xml.contains("<error severity=\"info\""));
+    }
+}
@@ -0,0 +1,160 @@
+//! GitLab Code Quality output, the Code Climate issue format.
+//!
+//! GitLab matches issues between the source and target branch of a merge request
+//! by fingerprint, so the fingerprint has to survive unrelated edits: it hashes
+//! the dimension, path and title, but not the line, which moves whenever code is
+//! added above the finding. Findings that would collide get an occurrence number.
+
+use serde_json::{json, Value};
+
+use super::{normalize_path, rule_id};
+use crate::appserver::protocol::{Dimension, Finding, ReviewOutput, Severity};
+
+/// Render `review` as a pretty-printed Code Quality report (a JSON array of issues).
+pub fn render(review: &ReviewOutput) -> String {
+    let mut seen: Vec<u64> = Vec::new();
+    let issues: Vec<Value> = review
+        .findings
+        .iter()
+        .map(|finding| {
+            let base = fingerprint(finding);
+            let occurrence = seen.iter().filter(|&&fp| fp == base).count();
+            seen.push(base);
+            issue(finding, fingerprint_hex(base, occurrence))
+        })
+        .collect();
+    serde_json::to_string_pretty(&issues).expect("Code Quality report is plain JSON")
+}
+
+/// Code Climate severity: `blocker` is not used, since the review has no
+/// equivalent of "must not merge".
+pub fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "critical",
+        Severity::High => "major",
+        Severity::Medium => "minor",
+        Severity::Low => "info",
+    }
+}
+
+/// Code Climate category for a dimension.
+fn category(dimension: Dimension) -> &'static str {
+    match dimension {
+        Dimension::Bugs => "Bug Risk",
+        Dimension::Security => "Security",
+        Dimension::Performance => "Performance",
+        Dimension::CodeQuality => "Clarity",
+        Dimension::Refactoring => "Complexity",
+    }
+}
+
+fn issue(finding: &Finding, fingerprint: String) -> Value {
+    json!({
+        "type": "issue",
+        "check_name": format!("codex-review/{}", rule_id(finding.dimension)),
+        "description": finding.title,
+        "content": {
+            "body": format!("{}\n\n**Suggestion**: {}", finding.problem, finding.suggestion),
+        },
+        "categories": [category(finding.dimension)],
+        "severity": severity(finding.severity),
+        "fingerprint": fingerprint,
+        "location": {
+            "path": normalize_path(&finding.file),
+            // GitLab requires a line; findings about a whole file point at its top.
+            "lines": { "begin": finding.line.filter(|&line| line > 0).unwrap_or(1) },
+        },
+    })
+}
+
+/// FNV-1a over the fields that identify a finding independently of its line.
+fn fingerprint(finding: &Finding) -> u64 {
+    let title = finding
+        .title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    let key = format!(
+        "{}\0{}\0{}",
+        rule_id(finding.dimension),
+        normalize_path(&finding.file),
+        title
+    );
+    fnv1a(key.as_bytes())
+}
+
+/// 32 hex digits, like the MD5 fingerprints Code Climate engines produce. The
+/// second half separates repeated findings.
+fn fingerprint_hex(base: u64, occurrence: usize) -> String {
+    format!(
+        "{base:016x}{:016x}",
+        fnv1a(&(occurrence as u64).to_le_bytes()) ^ base
+    )
+}
+
+/// 64-bit FNV-1a: tiny, dependency-free and stable across releases and platforms.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::report::test_support::{finding, review, FindingBuilder};
+
+    fn issues(findings: impl IntoIterator<Item = FindingBuilder>) -> Vec<Value> {
+        serde_json::from_str(&render(&review(findings))).unwrap()
+    }
+
+    #[test]
+    fn fnv1a_matches_reference_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn issues_carry_gitlab_fields() {
+        let issues = issues([
+            finding("Unchecked index").line(42),
+            finding("Whole file").no_line(),
+        ]);
+        let issue = &issues[0];
+        assert_eq!(issue["check_name"], "codex-review/bugs");
+        assert_eq!(issue["severity"], "major");
+        assert_eq!(issue["location"]["path"], "src/main.rs");
+        assert_eq!(issue["location"]["lines"]["begin"], 42);
+        assert_eq!(issue["fingerprint"].as_str().unwrap().len(), 32);
+        assert_eq!(issues[1]["location"]["lines"]["begin"], 1);
+    }
+
+    #[test]
+    fn fingerprints_ignore_lines_and_separate_duplicates() {
+        let moved = issues([finding("Unchecked  index").line(42)]);
+        let original = issues([finding("unchecked index").line(7)]);
+        assert_eq!(moved[0]["fingerprint"], original[0]["fingerprint"]);
+
+        let twice = issues([finding("Same").line(1), finding("Same").line(9)]);
+        assert_ne!(twice[0]["fingerprint"], twice[1]["fingerprint"]);
+        assert_eq!(
+            twice[0]["fingerprint"],
+            issues([finding("Same").line(1)])[0]["fingerprint"]
+        );
+    }
+
+    #[test]
+    fn fingerprints_are_pinned() {
+        // Changing these re-keys every issue already known to GitLab.
+        let issues = issues([
+            finding("Unchecked index").line(42),
+            finding("Unchecked index").line(50),
+        ]);
+        assert_eq!(issues[0]["fingerprint"], "79dc0891366754e9d11bf0a31e7d6d2c");
+        assert_eq!(issues[1]["fingerprint"], "79dc0891366754e9f01139b82b4dbb4d");
+    }
+}
@@ -0,0 +1,322 @@
+//! Self-contained HTML report.
+//!
+//! One file with inline styles and script and no external requests, so it can be
+//! attached to a ticket or opened from disk. It shows the score, summary and
+//! strengths, then the findings as collapsible sections that can be filtered by
+//! severity and dimension. When the reviewed tree is at hand, each finding shows
+//! the source around its line.
+
+use std::path::{Component, Path};
+
+use super::{escape_xml, normalize_path};
+use crate::appserver::protocol::{Dimension, Finding, ReviewOutput, Severity};
+
+/// Lines of source shown on each side of a finding's line.
+pub const SNIPPET_CONTEXT_LINES: usize = 3;
+
+/// Source files larger than this are not read for snippets.
+const MAX_SOURCE_BYTES: u64 = 2 * 1024 * 1024;
+
+const SEVERITIES: [Severity; 4] = [
+    Severity::Critical,
+    Severity::High,
+    Severity::Medium,
+    Severity::Low,
+];
+
+const DIMENSIONS: [Dimension; 5] = [
+    Dimension::Bugs,
+    Dimension::Security,
+    Dimension::Performance,
+    Dimension::CodeQuality,
+    Dimension::Refactoring,
+];
+
+const STYLE: &str = r#"
+body {
+  font: 15px/1.5 system-ui, sans-serif; color: #1f2328;
+  margin: 2rem auto; max-width: 60rem; padding: 0 1rem;
+}
+h1 { margin-bottom: .25rem; }
+.score { font-size: 1.25rem; font-weight: 600; }
+.filters {
+  display: flex; flex-wrap: wrap; gap: .5rem 1.5rem;
+  margin: 1rem 0; padding: .75rem; background: #f6f8fa; border-radius: 6px;
+}
+.filters fieldset { border: 0; margin: 0; padding: 0; }
+.filters legend { font-weight: 600; }
+.filters label { margin-right: .75rem; white-space: nowrap; }
+details.finding {
+  border: 1px solid #d0d7de; border-left-width: 4px; border-radius: 6px;
+  margin: .5rem 0; padding: .5rem .75rem;
+}
+details.finding[data-severity="CRITICAL"] { border-left-color: #82071e; }
+details.finding[data-severity="HIGH"] { border-left-color: #cf222e; }
+details.finding[data-severity="MEDIUM"] { border-left-color: #bf8700; }
+details.finding[data-severity="LOW"] { border-left-color: #0969da; }
+summary { cursor: pointer; font-weight: 600; }
+.badge {
+  display: inline-block; font-size: .75rem; padding: 0 .4rem;
+  border-radius: 1rem; background: #eaeef2; margin-right: .25rem;
+}
+code, pre { font: 13px/1.45 ui-monospace, monospace; }
+pre.snippet { background: #f6f8fa; padding: .5rem 0; overflow-x: auto; border-radius: 6px; }
+pre.snippet span { display: block; padding: 0 .75rem; }
+pre.snippet .hit { background: #fff8c5; }
+pre.snippet .ln { display: inline; padding: 0; color: #6e7781; user-select: none; }
+"#;
+
+const SCRIPT: &str = r#"
+const boxes = [...document.querySelectorAll('.filters input')];
+function applyFilters() {
+  const on = new Set(boxes.filter(b => b.checked).map(b => b.value));
+  let shown = 0;
+  for (const f of document.querySelectorAll('details.finding')) {
+    f.hidden = !(on.has(f.dataset.severity) && on.has(f.dataset.dimension));
+    if (!f.hidden) shown++;
+  }
+  document.getElementById('shown').textContent = shown;
+}
+boxes.forEach(b => b.addEventListener('change', applyFilters));
+function expandAll(open) {
+  document.querySelectorAll('details.finding').forEach(d => d.open = open);
+}
+applyFilters();
+"#;
+
+/// Render `review` of session `title` as a standalone HTML page. Snippets are
+/// read from files under `source_root`, when given.
+pub fn render(review: &ReviewOutput, title: &str, source_root: Option<&Path>) -> String {
+    let title = escape_xml(title);
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>Code Review: {title}</title>\n"));
+    html.push_str(&format!("<style>{STYLE}</style>\n</head>\n<body>\n"));
+    html.push_str(&format!("<h1>Code Review: {title}</h1>\n"));
+    html.push_str(&format!(
+        "<p class=\"score\">Score: {}/10</p>\n",
+        review.score
+    ));
+    html.push_str(&format!(
+        "<h2>Summary</h2>\n<p>{}</p>\n",
+        escape_xml(&review.summary)
+    ));
+
+    if !review.strengths.is_empty() {
+        html.push_str("<h2>Strengths</h2>\n<ul>\n");
+        for strength in &review.strengths {
+            html.push_str(&format!("<li>{}</li>\n", escape_xml(strength)));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str(&format!(
+        "<h2>Findings (<span id=\"shown\">{0}</span> of {0})</h2>\n",
+        review.findings.len()
+    ));
+    if review.findings.is_empty() {
+        html.push_str("<p>No findings.</p>\n");
+    } else {
+        html.push_str(&filters(review));
+        for finding in &review.findings {
+            html.push_str(&finding_section(finding, source_root));
+        }
+        html.push_str(&format!("<script>{SCRIPT}</script>\n"));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Checkboxes for each severity and dimension, with the number of findings.
+fn filters(review: &ReviewOutput) -> String {
+    let checkbox = |value: String, count: usize| {
+        format!(
+            "<label><input type=\"checkbox\" value=\"{value}\" checked> {value} ({count})</label>\n"
+        )
+    };
+    let mut html = String::from("<div class=\"filters\">\n<fieldset><legend>Severity</legend>\n");
+    for severity in SEVERITIES {
+        let count = review
+            .findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count();
+        html.push_str(&checkbox(severity.to_string(), count));
+    }
+    html.push_str("</fieldset>\n<fieldset><legend>Dimension</legend>\n");
+    for dimension in DIMENSIONS {
+        let count = review
+            .findings
+            .iter()
+            .filter(|f| f.dimension == dimension)
+            .count();
+        html.push_str(&checkbox(dimension.to_string(), count));
+    }
+    html.push_str("</fieldset>\n<div>\n");
+    html.push_str("<button type=\"button\" onclick=\"expandAll(true)\">Expand all</button>\n");
+    html.push_str("<button type=\"button\" onclick=\"expandAll(false)\">Collapse all</button>\n");
+    html.push_str("</div>\n</div>\n");
+    html
+}
+
+fn finding_section(finding: &Finding, source_root: Option<&Path>) -> String {
+    let path = normalize_path(&finding.file);
+    let location = match finding.line {
+        Some(line) => format!("{path}:{line}"),
+        None => path.clone(),
+    };
+    // Serious findings start open; the rest are one click away.
+    let open = if finding.severity.at_least(Severity::High) {
+        " open"
+    } else {
+        ""
+    };
+    let mut html = format!(
+        "<details class=\"finding\" data-severity=\"{}\" data-dimension=\"{}\"{open}>\n",
+        finding.severity, finding.dimension
+    );
+    html.push_str(&format!(
+        "<summary><span class=\"badge\">{}</span><span class=\"badge\">{}</span> {}</summary>\n",
+        finding.severity,
+        finding.dimension,
+        escape_xml(&finding.title)
+    ));
+    html.push_str(&format!("<p><code>{}</code></p>\n", escape_xml(&location)));
+    if let (Some(root), Some(line)) = (source_root, finding.line) {
+        if let Some(snippet) = snippet(root, &path, line as usize) {
+            html.push_str(&snippet);
+        }
+    }
+    html.push_str(&format!(
+        "<p><strong>Problem</strong>: {}</p>\n<p><strong>Suggestion</strong>: {}</p>\n</details>\n",
+        escape_xml(&finding.problem),
+        escape_xml(&finding.suggestion)
+    ));
+    html
+}
+
+/// The lines around `line` (1-based) of `path` under `root`, with `line` marked.
+/// `None` if the file is outside `root` (symlinks included), unreadable, too large
+/// or too short.
+fn snippet(root: &Path, path: &str, line: usize) -> Option<String> {
+    let relative = Path::new(path);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+    // The report is meant to be shared: never follow a link out of the project.
+    let root = root.canonicalize().ok()?;
+    let file = root.join(relative).canonicalize().ok()?;
+    if !file.starts_with(&root) {
+        return None;
+    }
+    if std::fs::metadata(&file).ok()?.len() > MAX_SOURCE_BYTES {
+        return None;
+    }
+    let source = std::fs::read_to_string(&file).ok()?;
+    let lines: Vec<&str> = source.lines().collect();
+    if line == 0 || line > lines.len() {
+        return None;
+    }
+    let first = line.saturating_sub(SNIPPET_CONTEXT_LINES).max(1);
+    let last = (line + SNIPPET_CONTEXT_LINES).min(lines.len());
+    let width = last.to_string().len();
+    let mut html = String::from("<pre class=\"snippet\">");
+    for n in first..=last {
+        let class = if n == line { " class=\"hit\"" } else { "" };
+        html.push_str(&format!(
+            "<span{class}><span class=\"ln\">{n:>width$}</span>  {}</span>",
+            escape_xml(lines[n - 1])
+        ));
+    }
+    html.push_str("</pre>\n");
+    Some(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::report::test_support::{finding, review, FindingBuilder};
+
+    fn unescaped(severity: Severity, file: &str) -> FindingBuilder {
+        finding("Unescaped <input>")
+            .dimension(Dimension::Security)
+            .severity(severity)
+            .file(file)
+    }
+
+    #[test]
+    fn renders_escaped_findings_with_filters() {
+        let review = ReviewOutput {
+            summary: "Needs <work>.".to_string(),
+            ..review([
+                unescaped(Severity::High, "src/a.rs").line(1),
+                unescaped(Severity::Low, "src/b.rs").no_line(),
+            ])
+        };
+        let html = render(&review, "session <1>", None);
+        assert!(html.contains("<title>Code Review: session &lt;1&gt;</title>"));
+        assert!(html.contains("Score: 7/10"));
+        assert!(html.contains("Needs &lt;work&gt;."));
+        assert!(html.contains("<li>Tests</li>"));
+        assert!(html.contains("value=\"HIGH\" checked> HIGH (1)"));
+        assert!(html.contains("value=\"Security\" checked> Security (2)"));
+        assert!(html.contains("data-severity=\"HIGH\" data-dimension=\"Security\" open>"));
+        assert!(html.contains("data-severity=\"LOW\" data-dimension=\"Security\">"));
+        assert!(html.contains("Unescaped &lt;input&gt;"));
+        assert!(!html.contains("<input>"));
+        assert!(!html.contains("http"), "report must not load anything");
+    }
+
+    #[test]
+    fn snippets_mark_the_line_and_stay_inside_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let source: String = (1..=10).map(|n| format!("line {n} <x>\n")).collect();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/a.rs"), source).unwrap();
+
+        let html = render(
+            &review([unescaped(Severity::Medium, "./src/a.rs").line(2)]),
+            "s",
+            Some(dir.path()),
+        );
+        assert!(html
+            .contains("<span class=\"hit\"><span class=\"ln\">2</span>  line 2 &lt;x&gt;</span>"));
+        assert!(html.contains(">1</span>  line 1"));
+        assert!(html.contains(">5</span>  line 5"));
+        assert!(!html.contains("line 6"));
+
+        assert!(snippet(dir.path(), "../etc/passwd", 1).is_none());
+        assert!(snippet(dir.path(), "/etc/passwd", 1).is_none());
+        assert!(snippet(dir.path(), "src/a.rs", 11).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn snippet_does_not_follow_symlinks_out_of_the_root() {
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "token = hunter2\n").unwrap();
+        let root = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("linked")).unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.txt"),
+            root.path().join("secret.txt"),
+        )
+        .unwrap();
+        std::fs::write(root.path().join("inside.txt"), "fine\n").unwrap();
+        std::os::unix::fs::symlink("inside.txt", root.path().join("alias.txt")).unwrap();
+
+        assert!(snippet(root.path(), "linked/secret.txt", 1).is_none());
+        assert!(snippet(root.path(), "secret.txt", 1).is_none());
+        assert!(snippet(root.path(), "alias.txt", 1).is_some());
+    }
+
+    #[test]
+    fn review_without_findings_has_no_filters() {
+        let html = render(&review([]), "s", None);
+        assert!(html.contains("No findings."));
+        assert!(!html.contains("<script>"));
+    }
+}
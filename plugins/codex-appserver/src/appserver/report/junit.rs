@@ -0,0 +1,146 @@
+//! JUnit XML output.
+//!
+//! Each file with findings becomes a test case named after the file. Findings at
+//! least as severe as [`ReportOptions::fail_on`] are `<failure>`s of that case;
+//! the rest are listed in its `<system-out>`, so the case passes. A review without
+//! findings is a single passing case, so dashboards still show the run.
+
+use super::{escape_xml, group_by_file, normalize_path, ReportOptions, TOOL_NAME};
+use crate::appserver::protocol::{Finding, ReviewOutput};
+
+/// Name of the test suite, and the class name of its test cases.
+pub const SUITE_NAME: &str = "codex-review";
+
+/// Render `review` as a JUnit XML document.
+pub fn render(review: &ReviewOutput, options: &ReportOptions) -> String {
+    let files = group_by_file(review);
+
+    let fails = |f: &Finding| f.severity.at_least(options.fail_on);
+    let tests = files.len().max(1);
+    let failures = files
+        .iter()
+        .filter(|(_, findings)| findings.iter().any(|f| fails(f)))
+        .count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{TOOL_NAME}\" tests=\"{tests}\" failures=\"{failures}\">\n"
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{SUITE_NAME}\" tests=\"{tests}\" failures=\"{failures}\" \
+         errors=\"0\" skipped=\"0\">\n"
+    ));
+    xml.push_str("    <properties>\n");
+    xml.push_str(&format!(
+        "      <property name=\"score\" value=\"{}\"/>\n",
+        review.score
+    ));
+    xml.push_str(&format!(
+        "      <property name=\"fail_on\" value=\"{}\"/>\n",
+        options.fail_on
+    ));
+    xml.push_str("    </properties>\n");
+
+    if files.is_empty() {
+        xml.push_str(&format!(
+            "    <testcase classname=\"{SUITE_NAME}\" name=\"review\">\n      \
+             <system-out>{}</system-out>\n    </testcase>\n",
+            escape_xml(&review.summary)
+        ));
+    }
+    for (file, findings) in &files {
+        let file = escape_xml(file);
+        xml.push_str(&format!(
+            "    <testcase classname=\"{SUITE_NAME}\" name=\"{file}\" file=\"{file}\">\n"
+        ));
+        for finding in findings.iter().filter(|f| fails(f)) {
+            xml.push_str(&format!(
+                "      <failure type=\"{}\" message=\"{}\">{}</failure>\n",
+                finding.severity,
+                escape_xml(&headline(finding)),
+                escape_xml(&details(finding))
+            ));
+        }
+        let passing: Vec<String> = findings
+            .iter()
+            .filter(|f| !fails(f))
+            .map(|f| format!("{}\n{}", headline(f), details(f)))
+            .collect();
+        if !passing.is_empty() {
+            xml.push_str(&format!(
+                "      <system-out>{}</system-out>\n",
+                escape_xml(&passing.join("\n\n"))
+            ));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// `[HIGH] [Bugs] Title`
+fn headline(finding: &Finding) -> String {
+    format!(
+        "[{}] [{}] {}",
+        finding.severity, finding.dimension, finding.title
+    )
+}
+
+fn details(finding: &Finding) -> String {
+    let location = match finding.line {
+        Some(line) => format!("{}:{line}", normalize_path(&finding.file)),
+        None => normalize_path(&finding.file),
+    };
+    format!(
+        "{location}\nProblem: {}\nSuggestion: {}",
+        finding.problem, finding.suggestion
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::protocol::Severity;
+    use crate::appserver::report::test_support::{finding, review, FindingBuilder};
+
+    fn at(file: &str, severity: Severity, title: &str) -> FindingBuilder {
+        finding(title).file(file).severity(severity).line(3)
+    }
+
+    #[test]
+    fn one_case_per_file_failing_only_above_threshold() {
+        let xml = render(
+            &review([
+                at("src/a.rs", Severity::High, "<bad>"),
+                at("./src/a.rs", Severity::Low, "nit"),
+                at("src/b.rs", Severity::Medium, "meh"),
+            ]),
+            &ReportOptions::default(),
+        );
+        assert!(xml.contains("tests=\"2\" failures=\"1\""), "{xml}");
+        assert_eq!(xml.matches("<testcase ").count(), 2);
+        assert_eq!(xml.matches("<failure ").count(), 1);
+        assert!(xml.contains("message=\"[HIGH] [Bugs] &lt;bad&gt;\""));
+        assert!(xml.contains("<system-out>[LOW] [Bugs] nit\nsrc/a.rs:3"));
+
+        let strict = render(
+            &review([at("src/b.rs", Severity::Medium, "meh")]),
+            &ReportOptions {
+                fail_on: Severity::Medium,
+            },
+        );
+        assert!(strict.contains("failures=\"1\""));
+    }
+
+    #[test]
+    fn review_without_findings_is_one_passing_case() {
+        let review = ReviewOutput {
+            summary: "Summary & notes".to_string(),
+            ..review([])
+        };
+        let xml = render(&review, &ReportOptions::default());
+        assert!(xml.contains("tests=\"1\" failures=\"0\""));
+        assert!(xml.contains("name=\"review\""));
+        assert!(xml.contains("Summary &amp; notes"));
+    }
+}
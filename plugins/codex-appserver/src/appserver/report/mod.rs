@@ -0,0 +1,248 @@
+//! Renderings of a [`ReviewOutput`] for tools other than people: code scanning,
+//! CI dashboards and editors.
+//!
+//! Each format lives in its own module; [`ReportFormat`] names them for the
+//! command line and picks the file extension. The [`html`] report is for people
+//! and needs the reviewed tree for its snippets, so it has its own entry point.
+
+pub mod checkstyle;
+pub mod codequality;
+pub mod html;
+pub mod junit;
+pub mod rdjson;
+pub mod sarif;
+
+use super::protocol::{Dimension, Finding, ReviewOutput, Severity};
+
+/// Name of the tool, as reported in the generated files.
+pub const TOOL_NAME: &str = "codex-appserver-review";
+
+/// Settings shared by the report formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportOptions {
+    /// Findings at least this severe count as failures where the format has them
+    /// (JUnit); less severe ones are reported without failing.
+    pub fail_on: Severity,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            fail_on: Severity::High,
+        }
+    }
+}
+
+/// A report format the review can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportFormat {
+    /// SARIF 2.1.0, for code scanning and IDE SARIF viewers.
+    Sarif,
+    /// JUnit XML, one test case per file, for CI test dashboards.
+    Junit,
+    /// Checkstyle XML, for CI dashboards and lint aggregators.
+    Checkstyle,
+    /// GitLab Code Quality (Code Climate) JSON, shown inline in merge requests.
+    CodeQuality,
+    /// reviewdog Diagnostic JSON, a single `DiagnosticResult` document.
+    Rdjson,
+    /// reviewdog Diagnostic JSON Lines, one `Diagnostic` per line.
+    Rdjsonl,
+}
+
+impl ReportFormat {
+    /// Every format, in the order they are listed in help text.
+    pub const ALL: &'static [ReportFormat] = &[
+        ReportFormat::Sarif,
+        ReportFormat::Junit,
+        ReportFormat::Checkstyle,
+        ReportFormat::CodeQuality,
+        ReportFormat::Rdjson,
+        ReportFormat::Rdjsonl,
+    ];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            ReportFormat::Sarif => "sarif",
+            ReportFormat::Junit => "junit",
+            ReportFormat::Checkstyle => "checkstyle",
+            ReportFormat::CodeQuality => "codequality",
+            ReportFormat::Rdjson => "rdjson",
+            ReportFormat::Rdjsonl => "rdjsonl",
+        }
+    }
+
+    /// Look up a format by its command-line name.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.name() == name)
+    }
+
+    /// File name suffix, appended to the session name.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Sarif => "sarif",
+            ReportFormat::Junit => "junit.xml",
+            ReportFormat::Checkstyle => "checkstyle.xml",
+            ReportFormat::CodeQuality => "codequality.json",
+            ReportFormat::Rdjson => "rdjson",
+            ReportFormat::Rdjsonl => "rdjsonl",
+        }
+    }
+
+    /// Render `review` in this format.
+    pub fn render(self, review: &ReviewOutput, options: &ReportOptions) -> String {
+        match self {
+            ReportFormat::Sarif => sarif::render(review),
+            ReportFormat::Junit => junit::render(review, options),
+            ReportFormat::Checkstyle => checkstyle::render(review),
+            ReportFormat::CodeQuality => codequality::render(review),
+            ReportFormat::Rdjson => rdjson::render(review),
+            ReportFormat::Rdjsonl => rdjson::render_lines(review),
+        }
+    }
+}
+
+impl std::fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Stable rule id for a dimension, e.g. `code-quality`.
+pub fn rule_id(dimension: Dimension) -> &'static str {
+    match dimension {
+        Dimension::Bugs => "bugs",
+        Dimension::Security => "security",
+        Dimension::Performance => "performance",
+        Dimension::CodeQuality => "code-quality",
+        Dimension::Refactoring => "refactoring",
+    }
+}
+
+/// A finding's file as a repository-relative path with forward slashes.
+pub fn normalize_path(file: &str) -> String {
+    let path = file.trim().replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    path.to_string()
+}
+
+/// Findings grouped by normalized path, in order of each file's first finding.
+pub fn group_by_file(review: &ReviewOutput) -> Vec<(String, Vec<&Finding>)> {
+    let mut files: Vec<(String, Vec<&Finding>)> = Vec::new();
+    for finding in &review.findings {
+        let path = normalize_path(&finding.file);
+        match files.iter_mut().find(|(file, _)| *file == path) {
+            Some((_, findings)) => findings.push(finding),
+            None => files.push((path, vec![finding])),
+        }
+    }
+    files
+}
+
+/// Escape text for use in XML content and double-quoted attributes.
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            // Control characters other than tab and newlines are not allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Review fixtures shared by the format tests.
+#[cfg(test)]
+pub(crate) mod test_support {
+    use crate::appserver::protocol::{Dimension, Finding, ReviewOutput, Severity};
+
+    /// Builds a [`Finding`]; starts as a high-severity bug at `./src/main.rs:42`.
+    pub(crate) struct FindingBuilder(Finding);
+
+    pub(crate) fn finding(title: &str) -> FindingBuilder {
+        FindingBuilder(Finding {
+            severity: Severity::High,
+            dimension: Dimension::Bugs,
+            title: title.to_string(),
+            file: "./src/main.rs".to_string(),
+            line: Some(42),
+            problem: "May panic.".to_string(),
+            suggestion: "Use get().".to_string(),
+        })
+    }
+
+    impl FindingBuilder {
+        pub(crate) fn severity(mut self, severity: Severity) -> Self {
+            self.0.severity = severity;
+            self
+        }
+
+        pub(crate) fn dimension(mut self, dimension: Dimension) -> Self {
+            self.0.dimension = dimension;
+            self
+        }
+
+        pub(crate) fn file(mut self, file: &str) -> Self {
+            self.0.file = file.to_string();
+            self
+        }
+
+        pub(crate) fn line(mut self, line: u32) -> Self {
+            self.0.line = Some(line);
+            self
+        }
+
+        /// A finding about the whole file.
+        pub(crate) fn no_line(mut self) -> Self {
+            self.0.line = None;
+            self
+        }
+    }
+
+    /// A review of `findings` scored 7, with a summary and one strength.
+    pub(crate) fn review(findings: impl IntoIterator<Item = FindingBuilder>) -> ReviewOutput {
+        ReviewOutput {
+            findings: findings.into_iter().map(|builder| builder.0).collect(),
+            score: 7,
+            summary: "Mostly fine.".to_string(),
+            strengths: vec!["Tests".to_string()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_round_trip_through_their_names() {
+        for &format in ReportFormat::ALL {
+            assert_eq!(ReportFormat::parse(format.name()), Some(format));
+        }
+        assert_eq!(ReportFormat::parse("xml"), None);
+    }
+
+    #[test]
+    fn xml_escaping_covers_markup_and_drops_control_characters() {
+        assert_eq!(
+            escape_xml("a < b && \"c\" > 'd'\u{1}\n"),
+            "a &lt; b &amp;&amp; &quot;c&quot; &gt; &#39;d&#39;\n"
+        );
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        assert_eq!(normalize_path("./src\\main.rs"), "src/main.rs");
+        assert_eq!(normalize_path(" src/lib.rs "), "src/lib.rs");
+    }
+}
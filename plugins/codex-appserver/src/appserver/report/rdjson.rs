@@ -0,0 +1,116 @@
+//! reviewdog Diagnostic Format output (`rdjson` and `rdjsonl`).
+//!
+//! Feed the file to `reviewdog -f=rdjson` (or `-f=rdjsonl`) to filter findings to
+//! the diff and post them with an existing reporter; this module only writes the
+//! file. Findings become diagnostics at their file and line, with severity ERROR
+//! (critical, high), WARNING (medium) or INFO (low), and the dimension as `code`.
+//!
+//! The finding's suggestion goes into the message. Codex writes suggestions as
+//! prose, not replacement code, so no reviewdog `suggestions` are emitted: reporters
+//! such as github-pr-review would offer to replace the line with that prose.
+
+use serde_json::{json, Value};
+
+use super::{normalize_path, rule_id};
+use crate::appserver::protocol::{Finding, ReviewOutput, Severity};
+
+/// `source.name` of every diagnostic.
+pub const SOURCE_NAME: &str = "codex-review";
+
+/// Render `review` as a pretty-printed `DiagnosticResult`.
+pub fn render(review: &ReviewOutput) -> String {
+    let result = json!({
+        "source": { "name": SOURCE_NAME },
+        "diagnostics": review.findings.iter().map(diagnostic).collect::<Vec<_>>(),
+    });
+    serde_json::to_string_pretty(&result).expect("rdjson is plain JSON")
+}
+
+/// Render `review` as one compact `Diagnostic` per line.
+pub fn render_lines(review: &ReviewOutput) -> String {
+    review
+        .findings
+        .iter()
+        .map(|finding| diagnostic(finding).to_string() + "\n")
+        .collect()
+}
+
+/// reviewdog severity for a finding severity.
+pub fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "ERROR",
+        Severity::Medium => "WARNING",
+        Severity::Low => "INFO",
+    }
+}
+
+fn diagnostic(finding: &Finding) -> Value {
+    let mut location = json!({ "path": normalize_path(&finding.file) });
+    if let Some(line) = finding.line.filter(|&line| line > 0) {
+        location["range"] = json!({ "start": { "line": line }, "end": { "line": line } });
+    }
+    json!({
+        "message": format!(
+            "[{}] {}\n\n{}\n\nSuggestion: {}",
+            finding.severity, finding.title, finding.problem, finding.suggestion
+        ),
+        "location": location,
+        "severity": severity(finding.severity),
+        "source": { "name": SOURCE_NAME },
+        "code": { "value": rule_id(finding.dimension) },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::protocol::Dimension;
+    use crate::appserver::report::test_support::{finding, review};
+
+    fn quadratic_loops() -> ReviewOutput {
+        let quadratic = || {
+            finding("Quadratic loop")
+                .dimension(Dimension::Performance)
+                .file("src\\lib.rs")
+        };
+        review([
+            quadratic().severity(Severity::Medium).line(12),
+            quadratic().severity(Severity::Low).no_line(),
+        ])
+    }
+
+    #[test]
+    fn diagnostics_have_locations_and_prose_suggestions_in_the_message() {
+        let result: Value = serde_json::from_str(&render(&quadratic_loops())).unwrap();
+        assert_eq!(result["source"]["name"], SOURCE_NAME);
+        let d = &result["diagnostics"][0];
+        assert_eq!(d["severity"], "WARNING");
+        assert_eq!(d["code"]["value"], "performance");
+        assert_eq!(d["location"]["path"], "src/lib.rs");
+        assert_eq!(d["location"]["range"]["start"]["line"], 12);
+        assert!(d.get("suggestions").is_none());
+        assert!(d["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("Suggestion: Use get()."));
+
+        let file_level = &result["diagnostics"][1];
+        assert_eq!(file_level["severity"], "INFO");
+        assert!(file_level["location"].get("range").is_none());
+        assert!(file_level.get("suggestions").is_none());
+        assert!(file_level["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("Suggestion: Use get()."));
+    }
+
+    #[test]
+    fn lines_format_has_one_diagnostic_per_line() {
+        let lines = render_lines(&quadratic_loops());
+        assert_eq!(lines.lines().count(), 2);
+        for line in lines.lines() {
+            let d: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(d["source"]["name"], SOURCE_NAME);
+        }
+    }
+}
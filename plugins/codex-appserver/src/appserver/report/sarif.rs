@@ -0,0 +1,161 @@
+//! SARIF 2.1.0 output.
+//!
+//! Each [`Dimension`] becomes a rule (`bugs`, `security`, ...) and each finding a
+//! result at its file and line, as a URI relative to the repository root. Severities
+//! map to SARIF levels: critical and high are errors, medium a warning, low a note.
+//!
+//! No `security-severity` is emitted: GitHub code scanning reads it only from the
+//! rule, and a rule here covers findings of every severity, so alerts are ranked by
+//! level instead.
+
+use serde_json::{json, Value};
+
+use super::{normalize_path, rule_id, TOOL_NAME};
+use crate::appserver::protocol::{Dimension, Finding, ReviewOutput, Severity};
+
+/// `$schema` of the generated log.
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Rules in `ruleIndex` order.
+const RULES: [Dimension; 5] = [
+    Dimension::Bugs,
+    Dimension::Security,
+    Dimension::Performance,
+    Dimension::CodeQuality,
+    Dimension::Refactoring,
+];
+
+/// Render `review` as a pretty-printed SARIF log with a single run.
+pub fn render(review: &ReviewOutput) -> String {
+    let log = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": TOOL_NAME,
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": RULES.iter().map(|&d| rule(d)).collect::<Vec<_>>(),
+                }
+            },
+            "results": review.findings.iter().map(result).collect::<Vec<_>>(),
+            "properties": {
+                "score": review.score,
+                "summary": review.summary,
+                "strengths": review.strengths,
+            }
+        }]
+    });
+    serde_json::to_string_pretty(&log).expect("SARIF log is plain JSON")
+}
+
+/// SARIF `level` for a severity.
+pub fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low => "note",
+    }
+}
+
+/// The rule's tag is its id; GitHub files results tagged `security` as security alerts.
+fn rule(dimension: Dimension) -> Value {
+    json!({
+        "id": rule_id(dimension),
+        "name": dimension.to_string(),
+        "shortDescription": { "text": format!("{dimension} findings from the Codex review") },
+        "properties": { "tags": [rule_id(dimension)] },
+    })
+}
+
+fn result(finding: &Finding) -> Value {
+    let mut region = json!({});
+    if let Some(line) = finding.line.filter(|&line| line > 0) {
+        region["startLine"] = json!(line);
+    }
+    let mut location = json!({
+        "artifactLocation": { "uri": normalize_path(&finding.file) }
+    });
+    if region != json!({}) {
+        location["region"] = region;
+    }
+    json!({
+        "ruleId": rule_id(finding.dimension),
+        "ruleIndex": RULES.iter().position(|&d| d == finding.dimension),
+        "level": level(finding.severity),
+        "message": {
+            "text": format!(
+                "{}\n\n{}\n\nSuggestion: {}",
+                finding.title, finding.problem, finding.suggestion
+            ),
+        },
+        "locations": [{ "physicalLocation": location }],
+        "properties": {
+            "severity": finding.severity,
+            "title": finding.title,
+            "suggestion": finding.suggestion,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::report::test_support::{finding, review};
+
+    #[test]
+    fn findings_become_results_with_locations() {
+        let sarif: Value = serde_json::from_str(&render(&review([
+            finding("Unchecked index").dimension(Dimension::Security),
+            finding("Magic number")
+                .severity(Severity::Low)
+                .dimension(Dimension::CodeQuality)
+                .no_line(),
+        ])))
+        .unwrap();
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][3]["id"], "code-quality");
+
+        let high = &run["results"][0];
+        assert_eq!(high["ruleId"], "security");
+        assert_eq!(high["ruleIndex"], 1);
+        assert_eq!(high["level"], "error");
+        let location = &high["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"],
+            json!({ "uri": "src/main.rs" })
+        );
+        assert_eq!(location["region"]["startLine"], 42);
+
+        let low = &run["results"][1];
+        assert_eq!(low["level"], "note");
+        assert!(low["locations"][0]["physicalLocation"]
+            .get("region")
+            .is_none());
+    }
+
+    #[test]
+    fn no_security_severity_on_results_or_rules() {
+        let sarif: Value = serde_json::from_str(&render(&review([finding("Injection")
+            .severity(Severity::Critical)
+            .dimension(Dimension::Security)])))
+        .unwrap();
+        let run = &sarif["runs"][0];
+        assert!(run["results"][0]["properties"]
+            .get("security-severity")
+            .is_none());
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert!(rules
+            .iter()
+            .all(|rule| rule["properties"].get("security-severity").is_none()));
+        assert_eq!(rules[1]["properties"]["tags"], json!(["security"]));
+    }
+
+    #[test]
+    fn empty_review_is_a_valid_log() {
+        let sarif: Value = serde_json::from_str(&render(&review([]))).unwrap();
+        assert_eq!(sarif["runs"][0]["results"], json!([]));
+        assert_eq!(sarif["runs"][0]["properties"]["score"], 7);
+    }
+}
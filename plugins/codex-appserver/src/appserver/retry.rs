@@ -0,0 +1,197 @@
+//! Retry policy for idempotent requests rejected with transient JSON-RPC errors.
+//!
+//! An overloaded app server sheds requests with [`BACKPRESSURE_ERROR_CODE`]; the
+//! request was not processed, so it is safe to send it again after a pause. The
+//! client applies the configured [`RetryPolicy`] to `initialize`, `thread/start`
+//! and `model/list`; other requests fail on the first error.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use super::error::{AppServerError, BACKPRESSURE_ERROR_CODE};
+
+/// Default number of attempts, including the first.
+pub const DEFAULT_RETRY_ATTEMPTS: u32 = 4;
+
+/// Default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Default upper bound on any single delay.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// How often, and how patiently, to retry a request.
+///
+/// The delay before retry `n` (1-based) is `initial_backoff * 2^(n-1)`, capped at
+/// `max_backoff`, then scaled by a random factor in `[1 - jitter, 1]` so that
+/// clients shed together do not come back together. A `retryAfterMs` hint in the
+/// error's `data` raises the delay (still capped).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) jitter: f64,
+    pub(crate) retryable_codes: Vec<i64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_RETRY_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter: 0.5,
+            retryable_codes: vec![BACKPRESSURE_ERROR_CODE],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Defaults: 4 attempts, 200ms doubling up to 5s, 50% jitter, retrying -32001.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Never retry.
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Total attempts, including the first (at least 1).
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before the first retry; later retries double it.
+    pub fn initial_backoff(mut self, delay: Duration) -> Self {
+        self.initial_backoff = delay;
+        self
+    }
+
+    /// Upper bound on any single delay.
+    pub fn max_backoff(mut self, delay: Duration) -> Self {
+        self.max_backoff = delay;
+        self
+    }
+
+    /// Fraction of each delay that is randomized, from 0.0 (none) to 1.0.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Also retry JSON-RPC errors with this code.
+    pub fn retryable_code(mut self, code: i64) -> Self {
+        if !self.retryable_codes.contains(&code) {
+            self.retryable_codes.push(code);
+        }
+        self
+    }
+
+    /// Whether `error` is worth another attempt.
+    pub fn is_retryable(&self, error: &AppServerError) -> bool {
+        error
+            .rpc_code()
+            .is_some_and(|code| self.retryable_codes.contains(&code))
+    }
+
+    /// Delay before retry number `retry` (1-based) after `error`.
+    pub fn delay(&self, retry: u32, error: &AppServerError) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff);
+        let backoff = backoff.mul_f64(1.0 - self.jitter * random_unit());
+        backoff.max(retry_after_hint(error)).min(self.max_backoff)
+    }
+}
+
+/// The server's `retryAfterMs` hint, or zero.
+fn retry_after_hint(error: &AppServerError) -> Duration {
+    match error {
+        AppServerError::Rpc { error, .. } => error
+            .data
+            .as_ref()
+            .and_then(|data| data.get("retryAfterMs"))
+            .and_then(|ms| ms.as_u64())
+            .map(Duration::from_millis)
+            .unwrap_or_default(),
+        _ => Duration::ZERO,
+    }
+}
+
+/// A number in `[0, 1)`, random enough for jitter. `RandomState` is seeded per
+/// instance, which avoids a dependency on a random number crate.
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appserver::protocol::JsonRpcError;
+    use serde_json::json;
+
+    fn rpc(code: i64, data: Option<serde_json::Value>) -> AppServerError {
+        AppServerError::Rpc {
+            method: "model/list".to_string(),
+            error: JsonRpcError {
+                code,
+                message: "Server overloaded".to_string(),
+                data,
+            },
+        }
+    }
+
+    #[test]
+    fn retries_only_configured_codes() {
+        let policy = RetryPolicy::new();
+        assert!(policy.is_retryable(&rpc(BACKPRESSURE_ERROR_CODE, None)));
+        assert!(!policy.is_retryable(&rpc(-32600, None)));
+        assert!(!policy.is_retryable(&AppServerError::Protocol("x".to_string())));
+        assert!(policy
+            .retryable_code(-32603)
+            .is_retryable(&rpc(-32603, None)));
+    }
+
+    #[test]
+    fn backoff_doubles_and_is_capped_without_jitter() {
+        let policy = RetryPolicy::new()
+            .jitter(0.0)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(350));
+        let err = rpc(BACKPRESSURE_ERROR_CODE, None);
+        assert_eq!(policy.delay(1, &err), Duration::from_millis(100));
+        assert_eq!(policy.delay(2, &err), Duration::from_millis(200));
+        assert_eq!(policy.delay(3, &err), Duration::from_millis(350));
+        assert_eq!(policy.delay(40, &err), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = RetryPolicy::new().initial_backoff(Duration::from_millis(100));
+        let err = rpc(BACKPRESSURE_ERROR_CODE, None);
+        for _ in 0..100 {
+            let delay = policy.delay(1, &err);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn retry_after_hint_raises_delay_up_to_cap() {
+        let policy = RetryPolicy::new()
+            .jitter(0.0)
+            .initial_backoff(Duration::from_millis(10));
+        let hinted = rpc(BACKPRESSURE_ERROR_CODE, Some(json!({"retryAfterMs": 750})));
+        assert_eq!(policy.delay(1, &hinted), Duration::from_millis(750));
+        let huge = rpc(
+            BACKPRESSURE_ERROR_CODE,
+            Some(json!({"retryAfterMs": 60_000})),
+        );
+        assert_eq!(policy.delay(1, &huge), DEFAULT_MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,276 @@
+//! Capture of the app server's stderr.
+//!
+//! A background task drains the child's stderr so the pipe never fills, keeps the
+//! most recent lines in a bounded ring buffer, and optionally tees the raw bytes
+//! to a log file and to our own stderr. The tail is attached to errors when the
+//! server exits or stops answering, which is usually where the real cause is.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Notify;
+
+use super::client::truncate_to_char_boundary;
+use super::transport::BoxedReader;
+
+/// Default size of the stderr ring buffer (16 KB).
+pub const DEFAULT_STDERR_TAIL_BYTES: usize = 16 * 1024;
+
+/// Lines of stderr attached to an [`AppServerError`](super::AppServerError).
+pub const ERROR_STDERR_TAIL_LINES: usize = 20;
+
+/// Most recent stderr lines of the app server. Cheap to clone; clones share state.
+#[derive(Clone)]
+pub struct StderrBuffer {
+    inner: Arc<StderrState>,
+}
+
+struct StderrState {
+    ring: Mutex<Ring>,
+    /// Set once the capture task has seen EOF.
+    closed: AtomicBool,
+    closed_notify: Notify,
+}
+
+struct Ring {
+    lines: VecDeque<String>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl StderrBuffer {
+    /// Empty buffer keeping at most `max_bytes` of line text (at least one line,
+    /// itself truncated to `max_bytes`).
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            inner: Arc::new(StderrState {
+                ring: Mutex::new(Ring {
+                    lines: VecDeque::new(),
+                    bytes: 0,
+                    max_bytes,
+                }),
+                closed: AtomicBool::new(false),
+                closed_notify: Notify::new(),
+            }),
+        }
+    }
+
+    /// Append a line, evicting the oldest lines once the buffer is over budget.
+    pub fn push_line(&self, line: &str) {
+        let mut ring = self.inner.ring.lock().unwrap();
+        let line = truncate_to_char_boundary(line, ring.max_bytes).to_string();
+        while ring.bytes + line.len() > ring.max_bytes {
+            match ring.lines.pop_front() {
+                Some(old) => ring.bytes -= old.len(),
+                None => break,
+            }
+        }
+        ring.bytes += line.len();
+        ring.lines.push_back(line);
+    }
+
+    /// All buffered lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.inner
+            .ring
+            .lock()
+            .unwrap()
+            .lines
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// The last `max_lines` lines joined with newlines, or `None` if nothing was captured.
+    pub fn tail(&self, max_lines: usize) -> Option<String> {
+        let ring = self.inner.ring.lock().unwrap();
+        let skip = ring.lines.len().saturating_sub(max_lines);
+        let tail: Vec<&str> = ring.lines.iter().skip(skip).map(String::as_str).collect();
+        (!tail.is_empty()).then(|| tail.join("\n"))
+    }
+
+    /// Whether the server's stderr has been fully drained.
+    pub fn is_closed(&self) -> bool {
+        self.inner.closed.load(Ordering::SeqCst)
+    }
+
+    /// Wait until stderr is fully drained, at most `timeout`. Returns whether it was.
+    pub async fn wait_closed(&self, timeout: Duration) -> bool {
+        let notified = self.inner.closed_notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if self.is_closed() {
+            return true;
+        }
+        tokio::time::timeout(timeout, notified).await.is_ok()
+    }
+
+    fn close(&self) {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.closed_notify.notify_waiters();
+    }
+
+    fn max_line_bytes(&self) -> usize {
+        self.inner.ring.lock().unwrap().max_bytes
+    }
+}
+
+/// Capture task: drain `reader` into `buffer` until EOF, teeing raw bytes to
+/// `log_path` (appended) and, if `echo` is set, to this process's stderr.
+pub(crate) async fn capture_stderr(
+    reader: BoxedReader,
+    buffer: StderrBuffer,
+    log_path: Option<PathBuf>,
+    echo: bool,
+) {
+    let mut log = match &log_path {
+        Some(path) => open_log(path).await,
+        None => None,
+    };
+    let mut echo_out = echo.then(tokio::io::stderr);
+    let max_line_bytes = buffer.max_line_bytes();
+    let mut reader = BufReader::new(reader);
+    let mut line: Vec<u8> = Vec::new();
+
+    loop {
+        let chunk = match reader.fill_buf().await {
+            Ok([]) => break, // EOF
+            Ok(chunk) => chunk,
+            Err(e) => {
+                eprintln!("[appserver-stderr] read error: {e}");
+                break;
+            }
+        };
+        let (piece, complete) = match chunk.iter().position(|&b| b == b'\n') {
+            Some(pos) => (&chunk[..=pos], true),
+            None => (chunk, false),
+        };
+
+        if let Some(file) = log.as_mut() {
+            if let Err(e) = file.write_all(piece).await {
+                eprintln!("[appserver-stderr] stopped writing log: {e}");
+                log = None;
+            }
+        }
+        if let Some(out) = echo_out.as_mut() {
+            let _ = out.write_all(piece).await;
+        }
+
+        // Keep at most `max_line_bytes` of each line; the rest is only teed.
+        let text = piece.strip_suffix(b"\n").unwrap_or(piece);
+        let room = max_line_bytes.saturating_sub(line.len());
+        line.extend_from_slice(&text[..text.len().min(room)]);
+
+        let consumed = piece.len();
+        reader.consume(consumed);
+        if complete {
+            push_raw_line(&buffer, &line);
+            line.clear();
+        }
+    }
+
+    if !line.is_empty() {
+        push_raw_line(&buffer, &line);
+    }
+    if let Some(file) = log.as_mut() {
+        let _ = file.flush().await;
+    }
+    buffer.close();
+}
+
+fn push_raw_line(buffer: &StderrBuffer, line: &[u8]) {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    buffer.push_line(&String::from_utf8_lossy(line));
+}
+
+/// Open `path` for appending, creating parent directories. Failures are logged and
+/// disable the tee rather than the capture.
+async fn open_log(path: &Path) -> Option<File> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Err(e) = tokio::fs::create_dir_all(parent).await {
+            eprintln!("[appserver-stderr] cannot create {}: {e}", parent.display());
+            return None;
+        }
+    }
+    match tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+    {
+        Ok(file) => Some(file),
+        Err(e) => {
+            eprintln!("[appserver-stderr] cannot open {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_evicts_oldest_lines_over_budget() {
+        let buffer = StderrBuffer::new(10);
+        for line in ["aaaa", "bbbb", "cccc"] {
+            buffer.push_line(line);
+        }
+        assert_eq!(buffer.lines(), vec!["bbbb", "cccc"]);
+    }
+
+    #[test]
+    fn oversized_line_is_truncated_not_dropped() {
+        let buffer = StderrBuffer::new(4);
+        buffer.push_line("first");
+        buffer.push_line("한글한글");
+        assert_eq!(buffer.lines(), vec!["한"]);
+    }
+
+    #[test]
+    fn tail_returns_last_lines_or_none() {
+        let buffer = StderrBuffer::new(1024);
+        assert_eq!(buffer.tail(2), None);
+        for line in ["one", "two", "three"] {
+            buffer.push_line(line);
+        }
+        assert_eq!(buffer.tail(2).as_deref(), Some("two\nthree"));
+        assert_eq!(buffer.tail(10).as_deref(), Some("one\ntwo\nthree"));
+    }
+
+    #[tokio::test]
+    async fn capture_splits_lines_tees_log_and_closes() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("logs/stderr.log");
+        let (mut write, read) = tokio::io::duplex(64);
+        let buffer = StderrBuffer::new(1024);
+        let task = tokio::spawn(capture_stderr(
+            Box::new(read),
+            buffer.clone(),
+            Some(log_path.clone()),
+            false,
+        ));
+
+        write
+            .write_all(b"warn: one\r\npanicked at 'boom'\npartial")
+            .await
+            .unwrap();
+        drop(write);
+        assert!(buffer.wait_closed(Duration::from_secs(5)).await);
+        task.await.unwrap();
+
+        assert_eq!(
+            buffer.lines(),
+            vec!["warn: one", "panicked at 'boom'", "partial"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(&log_path).unwrap(),
+            "warn: one\r\npanicked at 'boom'\npartial"
+        );
+    }
+}
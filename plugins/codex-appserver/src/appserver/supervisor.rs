@@ -0,0 +1,131 @@
+//! Supervised connection: replaces the app server after it exits.
+//!
+//! A [`SupervisedClient`] owns a connector (by default: spawn `codex app-server`
+//! from a [`ClientConfig`]) and the `initialize` parameters. When the current
+//! connection has hit EOF, [`SupervisedClient::reconnect`] starts a fresh server and
+//! repeats the handshake; [`SupervisedClient::resume_thread`] then reopens a thread
+//! by id, since threads outlive the process that created them. Requests that were
+//! in flight when the server died are not replayed — callers decide what to retry.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::sync::Mutex;
+
+use super::client::{CodexAppServerClient, ShutdownStatus};
+use super::config::ClientConfig;
+use super::error::AppServerError;
+use super::protocol::{
+    InitializeParams, ThreadResumeParams, ThreadStartParams, ThreadStartResponse,
+};
+
+type ConnectFuture =
+    Pin<Box<dyn Future<Output = Result<CodexAppServerClient, AppServerError>> + Send>>;
+
+/// Produces a new, not yet initialized connection.
+type Connector = Box<dyn Fn() -> ConnectFuture + Send + Sync>;
+
+struct SupervisorState {
+    client: CodexAppServerClient,
+    restarts: u32,
+}
+
+/// A client connection that can be re-established after the server exits.
+pub struct SupervisedClient {
+    connect: Connector,
+    init_params: InitializeParams,
+    state: Mutex<SupervisorState>,
+}
+
+impl SupervisedClient {
+    /// Spawn the app server described by `config` and run the handshake.
+    /// Reconnects spawn a new process from the same config.
+    pub async fn spawn(
+        config: ClientConfig,
+        init_params: InitializeParams,
+    ) -> Result<Self, AppServerError> {
+        Self::with_connector(
+            move || {
+                let config = config.clone();
+                async move { CodexAppServerClient::spawn_with_config(&config).await }
+            },
+            init_params,
+        )
+        .await
+    }
+
+    /// Like [`spawn`](Self::spawn), with a custom way of opening connections
+    /// (e.g. a socket transport or an in-memory server).
+    pub async fn with_connector<F, Fut>(
+        connect: F,
+        init_params: InitializeParams,
+    ) -> Result<Self, AppServerError>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<CodexAppServerClient, AppServerError>> + Send + 'static,
+    {
+        let connect: Connector = Box::new(move || Box::pin(connect()));
+        let client = connect_and_initialize(&connect, &init_params).await?;
+        Ok(Self {
+            connect,
+            init_params,
+            state: Mutex::new(SupervisorState {
+                client,
+                restarts: 0,
+            }),
+        })
+    }
+
+    /// Handle to the current connection.
+    pub async fn client(&self) -> CodexAppServerClient {
+        self.state.lock().await.client.clone()
+    }
+
+    /// Number of times the server has been replaced.
+    pub async fn restarts(&self) -> u32 {
+        self.state.lock().await.restarts
+    }
+
+    /// Replace the connection if the server has closed it, re-running the handshake,
+    /// and return the live handle. A connection that is still open is returned as is,
+    /// so concurrent callers noticing the same crash restart the server only once.
+    pub async fn reconnect(&self) -> Result<CodexAppServerClient, AppServerError> {
+        let mut state = self.state.lock().await;
+        if !state.client.is_closed() {
+            return Ok(state.client.clone());
+        }
+        let client = connect_and_initialize(&self.connect, &self.init_params).await?;
+        state.restarts += 1;
+        // The old handle's process has exited; dropping it releases the rest.
+        state.client = client.clone();
+        Ok(client)
+    }
+
+    /// Reopen a thread on the current connection via `thread/resume`, reapplying
+    /// `settings` (typically those the thread was started with).
+    pub async fn resume_thread(
+        &self,
+        thread_id: &str,
+        settings: &ThreadStartParams,
+    ) -> Result<ThreadStartResponse, AppServerError> {
+        let params = ThreadResumeParams {
+            thread_id: thread_id.to_string(),
+            settings: settings.clone(),
+        };
+        self.client().await.thread_resume(&params).await
+    }
+
+    /// Gracefully shut down the current server.
+    pub async fn shutdown(self) -> ShutdownStatus {
+        self.state.into_inner().client.shutdown().await
+    }
+}
+
+async fn connect_and_initialize(
+    connect: &Connector,
+    init_params: &InitializeParams,
+) -> Result<CodexAppServerClient, AppServerError> {
+    let client = connect().await?;
+    client.initialize(init_params).await?;
+    Ok(client)
+}
@@ -0,0 +1,643 @@
+//! Turn transcripts: what the agent did during one turn, assembled from events.
+//!
+//! The server reports each unit of work as a thread item: `item/started`, then
+//! streaming deltas, then `item/completed` with the final state. [`TurnTranscript`]
+//! folds those [`ServerEvent`]s into one entry per item, in start order, so a
+//! review can be audited afterwards: the commands run (with exit codes and
+//! output), the files read, the reasoning summaries and the agent's messages.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::client::truncate_to_char_boundary;
+use super::events::ServerEvent;
+
+/// Command output kept per command execution; the rest is dropped.
+pub const MAX_COMMAND_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// Agent message and reasoning text kept per item (each of a reasoning item's
+/// summary and text); the rest is dropped.
+pub const MAX_ITEM_TEXT_BYTES: usize = 64 * 1024;
+
+/// One thread item of a turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptItem {
+    /// `text` is capped at [`MAX_ITEM_TEXT_BYTES`].
+    AgentMessage {
+        id: String,
+        text: String,
+        #[serde(default)]
+        truncated: bool,
+        completed: bool,
+    },
+    /// `summary` is the reasoning summary shown to users; `text` the raw reasoning,
+    /// when the server sends it. Both are capped at [`MAX_ITEM_TEXT_BYTES`].
+    Reasoning {
+        id: String,
+        summary: String,
+        text: String,
+        #[serde(default)]
+        truncated: bool,
+        completed: bool,
+    },
+    CommandExecution {
+        id: String,
+        command: String,
+        cwd: Option<String>,
+        /// Server-reported status: `inProgress`, `completed`, `failed`, `declined`.
+        status: Option<String>,
+        exit_code: Option<i64>,
+        duration_ms: Option<u64>,
+        /// Output, capped at [`MAX_COMMAND_OUTPUT_BYTES`].
+        output: String,
+        output_truncated: bool,
+        /// Paths the command read, as parsed by the server.
+        files_read: Vec<String>,
+        completed: bool,
+    },
+    /// Any other item type (file changes, web searches, tool calls), kept verbatim.
+    Other {
+        id: String,
+        item_type: String,
+        item: Value,
+        completed: bool,
+    },
+}
+
+impl TranscriptItem {
+    pub fn id(&self) -> &str {
+        match self {
+            TranscriptItem::AgentMessage { id, .. }
+            | TranscriptItem::Reasoning { id, .. }
+            | TranscriptItem::CommandExecution { id, .. }
+            | TranscriptItem::Other { id, .. } => id,
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        match self {
+            TranscriptItem::AgentMessage { completed, .. }
+            | TranscriptItem::Reasoning { completed, .. }
+            | TranscriptItem::CommandExecution { completed, .. }
+            | TranscriptItem::Other { completed, .. } => *completed,
+        }
+    }
+
+    /// Build an item from the server's item object. `None` if it has no id.
+    fn from_item(item: &Value, completed: bool) -> Option<Self> {
+        let id = item.get("id")?.as_str()?.to_string();
+        let str_of = |key: &str| item.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let joined = |key: &str| match item.get(key) {
+            Some(Value::Array(parts)) => parts
+                .iter()
+                .filter_map(|p| p.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Some(Value::String(s)) => s.clone(),
+            _ => String::new(),
+        };
+        let item_type = str_of("type").unwrap_or_default();
+        Some(match item_type.as_str() {
+            "agentMessage" => {
+                let (text, truncated) =
+                    capped(&str_of("text").unwrap_or_default(), MAX_ITEM_TEXT_BYTES);
+                TranscriptItem::AgentMessage {
+                    id,
+                    text,
+                    truncated,
+                    completed,
+                }
+            }
+            "reasoning" => {
+                let (summary, summary_truncated) = capped(&joined("summary"), MAX_ITEM_TEXT_BYTES);
+                let (text, text_truncated) = capped(&joined("content"), MAX_ITEM_TEXT_BYTES);
+                TranscriptItem::Reasoning {
+                    id,
+                    summary,
+                    text,
+                    truncated: summary_truncated || text_truncated,
+                    completed,
+                }
+            }
+            "commandExecution" => {
+                let (output, output_truncated) = capped(
+                    &str_of("aggregatedOutput").unwrap_or_default(),
+                    MAX_COMMAND_OUTPUT_BYTES,
+                );
+                TranscriptItem::CommandExecution {
+                    id,
+                    command: str_of("command").unwrap_or_default(),
+                    cwd: str_of("cwd"),
+                    status: str_of("status"),
+                    exit_code: item.get("exitCode").and_then(|v| v.as_i64()),
+                    duration_ms: item.get("durationMs").and_then(|v| v.as_u64()),
+                    output,
+                    output_truncated,
+                    files_read: read_paths(item),
+                    completed,
+                }
+            }
+            _ => TranscriptItem::Other {
+                id,
+                item_type,
+                item: item.clone(),
+                completed,
+            },
+        })
+    }
+
+    /// Replace this entry with a newer state of the same item (its start or its
+    /// completion). Text the newer state leaves empty keeps what the deltas
+    /// accumulated.
+    fn merge(&mut self, mut newer: TranscriptItem) {
+        use std::mem::take;
+        match (&mut *self, &mut newer) {
+            (
+                TranscriptItem::AgentMessage {
+                    text, truncated, ..
+                },
+                TranscriptItem::AgentMessage {
+                    text: final_text,
+                    truncated: final_truncated,
+                    ..
+                },
+            ) if final_text.is_empty() => {
+                *final_text = take(text);
+                *final_truncated = *truncated;
+            }
+            (
+                TranscriptItem::Reasoning {
+                    summary,
+                    text,
+                    truncated,
+                    ..
+                },
+                TranscriptItem::Reasoning {
+                    summary: final_summary,
+                    text: final_text,
+                    truncated: final_truncated,
+                    ..
+                },
+            ) => {
+                if final_summary.is_empty() {
+                    *final_summary = take(summary);
+                    *final_truncated |= *truncated;
+                }
+                if final_text.is_empty() {
+                    *final_text = take(text);
+                    *final_truncated |= *truncated;
+                }
+            }
+            (
+                TranscriptItem::CommandExecution {
+                    output,
+                    output_truncated,
+                    ..
+                },
+                TranscriptItem::CommandExecution {
+                    output: final_output,
+                    output_truncated: final_truncated,
+                    ..
+                },
+            ) if final_output.is_empty() => {
+                *final_output = take(output);
+                *final_truncated = *output_truncated;
+            }
+            _ => {}
+        }
+        *self = newer;
+    }
+}
+
+/// Paths from the `read` entries of a command's parsed `commandActions`.
+fn read_paths(item: &Value) -> Vec<String> {
+    let Some(Value::Array(actions)) = item.get("commandActions") else {
+        return Vec::new();
+    };
+    actions
+        .iter()
+        .filter(|a| a.get("type").and_then(|t| t.as_str()) == Some("read"))
+        .filter_map(|a| a.get("path").and_then(|p| p.as_str()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// `text` cut to `max` bytes, and whether anything was cut.
+fn capped(text: &str, max: usize) -> (String, bool) {
+    let kept = truncate_to_char_boundary(text, max);
+    (kept.to_string(), kept.len() < text.len())
+}
+
+/// Append as much of `delta` as fits in `max` bytes, noting in `truncated` when
+/// some of it did not.
+fn push_capped(text: &mut String, truncated: &mut bool, delta: &str, max: usize) {
+    let room = max.saturating_sub(text.len());
+    let take = truncate_to_char_boundary(delta, room);
+    text.push_str(take);
+    *truncated |= take.len() < delta.len();
+}
+
+/// Everything one turn did, built up from its events with [`TurnTranscript::apply`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TurnTranscript {
+    pub thread_id: String,
+    pub turn_id: String,
+    /// Items in the order they started.
+    pub items: Vec<TranscriptItem>,
+    /// Every file the agent's commands read, first read first, without repeats.
+    pub files_read: Vec<String>,
+    #[serde(skip)]
+    index: HashMap<String, usize>,
+}
+
+impl TurnTranscript {
+    pub fn new(thread_id: impl Into<String>, turn_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            turn_id: turn_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Fold one event into the transcript. Events of other threads or turns, and
+    /// events that are not about items, are ignored.
+    pub fn apply(&mut self, event: &ServerEvent) {
+        match event {
+            ServerEvent::ItemStarted {
+                thread_id,
+                turn_id,
+                item,
+            } if self.is_ours(thread_id, turn_id) => {
+                if let Some(started) = TranscriptItem::from_item(item, false) {
+                    self.upsert(started);
+                }
+            }
+            ServerEvent::ItemCompleted {
+                thread_id,
+                turn_id,
+                item,
+            } if self.is_ours(thread_id, turn_id) => {
+                if let Some(done) = TranscriptItem::from_item(item, true) {
+                    if let TranscriptItem::CommandExecution { files_read, .. } = &done {
+                        for path in files_read {
+                            if !self.files_read.contains(path) {
+                                self.files_read.push(path.clone());
+                            }
+                        }
+                    }
+                    self.upsert(done);
+                }
+            }
+            ServerEvent::AgentMessageDelta {
+                thread_id,
+                turn_id,
+                item_id: Some(item_id),
+                delta,
+            } if self.is_ours(thread_id, turn_id) => {
+                let placeholder = || TranscriptItem::AgentMessage {
+                    id: item_id.clone(),
+                    text: String::new(),
+                    truncated: false,
+                    completed: false,
+                };
+                if let TranscriptItem::AgentMessage {
+                    text, truncated, ..
+                } = self.entry(item_id, placeholder)
+                {
+                    push_capped(text, truncated, delta, MAX_ITEM_TEXT_BYTES);
+                }
+            }
+            ServerEvent::ReasoningDelta {
+                thread_id,
+                turn_id,
+                item_id: Some(item_id),
+                delta,
+                summary: is_summary,
+            } if self.is_ours(thread_id, turn_id) => {
+                let placeholder = || TranscriptItem::Reasoning {
+                    id: item_id.clone(),
+                    summary: String::new(),
+                    text: String::new(),
+                    truncated: false,
+                    completed: false,
+                };
+                if let TranscriptItem::Reasoning {
+                    summary,
+                    text,
+                    truncated,
+                    ..
+                } = self.entry(item_id, placeholder)
+                {
+                    let target = if *is_summary { summary } else { text };
+                    push_capped(target, truncated, delta, MAX_ITEM_TEXT_BYTES);
+                }
+            }
+            ServerEvent::CommandOutputDelta {
+                thread_id,
+                turn_id,
+                item_id: Some(item_id),
+                delta,
+            } if self.is_ours(thread_id, turn_id) => {
+                let placeholder = || TranscriptItem::CommandExecution {
+                    id: item_id.clone(),
+                    command: String::new(),
+                    cwd: None,
+                    status: None,
+                    exit_code: None,
+                    duration_ms: None,
+                    output: String::new(),
+                    output_truncated: false,
+                    files_read: Vec::new(),
+                    completed: false,
+                };
+                if let TranscriptItem::CommandExecution {
+                    output,
+                    output_truncated,
+                    ..
+                } = self.entry(item_id, placeholder)
+                {
+                    push_capped(output, output_truncated, delta, MAX_COMMAND_OUTPUT_BYTES);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The item with this id, if the transcript has seen it.
+    pub fn item(&self, id: &str) -> Option<&TranscriptItem> {
+        self.index.get(id).map(|&i| &self.items[i])
+    }
+
+    /// Command executions, in start order.
+    pub fn commands(&self) -> impl Iterator<Item = &TranscriptItem> {
+        self.items
+            .iter()
+            .filter(|item| matches!(item, TranscriptItem::CommandExecution { .. }))
+    }
+
+    /// Events without ids match any turn; the server omits them on some notifications.
+    fn is_ours(&self, thread_id: &Option<String>, turn_id: &Option<String>) -> bool {
+        thread_id.as_deref().is_none_or(|id| id == self.thread_id)
+            && turn_id.as_deref().is_none_or(|id| id == self.turn_id)
+    }
+
+    /// Add `item`, or merge it into the entry deltas or earlier events created.
+    /// A late `item/started` never reopens a completed item.
+    fn upsert(&mut self, item: TranscriptItem) {
+        match self.index.get(item.id()) {
+            Some(&i) if self.items[i].is_completed() && !item.is_completed() => {}
+            Some(&i) => self.items[i].merge(item),
+            None => {
+                self.index.insert(item.id().to_string(), self.items.len());
+                self.items.push(item);
+            }
+        }
+    }
+
+    /// The item with `id`, created with `placeholder` if a delta arrived before
+    /// its `item/started`.
+    fn entry(
+        &mut self,
+        id: &str,
+        placeholder: impl FnOnce() -> TranscriptItem,
+    ) -> &mut TranscriptItem {
+        let i = match self.index.get(id) {
+            Some(&i) => i,
+            None => {
+                self.upsert(placeholder());
+                self.items.len() - 1
+            }
+        };
+        &mut self.items[i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(method: &str, params: Value) -> ServerEvent {
+        ServerEvent::from_notification(method, &params)
+    }
+
+    fn transcript_of(events: &[ServerEvent]) -> TurnTranscript {
+        let mut transcript = TurnTranscript::new("thr_1", "turn_1");
+        for e in events {
+            transcript.apply(e);
+        }
+        transcript
+    }
+
+    #[test]
+    fn command_lifecycle_keeps_exit_code_output_and_reads() {
+        let started = json!({"id":"cmd_1","type":"commandExecution","command":"cat src/lib.rs","cwd":"/repo","status":"inProgress"});
+        let done = json!({
+            "id":"cmd_1","type":"commandExecution","command":"cat src/lib.rs","cwd":"/repo",
+            "status":"completed","exitCode":0,"durationMs":12,"aggregatedOutput":"fn main() {}\n",
+            "commandActions":[{"type":"read","command":"cat src/lib.rs","name":"lib.rs","path":"src/lib.rs"}]
+        });
+        let transcript = transcript_of(&[
+            event(
+                "item/started",
+                json!({"threadId":"thr_1","turnId":"turn_1","item":started}),
+            ),
+            event(
+                "item/commandExecution/outputDelta",
+                json!({"threadId":"thr_1","turnId":"turn_1","itemId":"cmd_1","delta":"fn main"}),
+            ),
+            event(
+                "item/completed",
+                json!({"threadId":"thr_1","turnId":"turn_1","item":done}),
+            ),
+        ]);
+
+        assert_eq!(transcript.items.len(), 1);
+        match &transcript.items[0] {
+            TranscriptItem::CommandExecution {
+                exit_code,
+                output,
+                files_read,
+                completed,
+                ..
+            } => {
+                assert_eq!(*exit_code, Some(0));
+                assert_eq!(output, "fn main() {}\n");
+                assert_eq!(files_read, &["src/lib.rs"]);
+                assert!(*completed);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert_eq!(transcript.files_read, ["src/lib.rs"]);
+    }
+
+    #[test]
+    fn delta_before_item_started_is_kept() {
+        let transcript = transcript_of(&[
+            event(
+                "item/agentMessage/delta",
+                json!({"turnId":"turn_1","itemId":"msg_1","delta":"Looking at "}),
+            ),
+            event(
+                "item/started",
+                json!({"turnId":"turn_1","item":{"id":"msg_1","type":"agentMessage","text":""}}),
+            ),
+            event(
+                "item/agentMessage/delta",
+                json!({"turnId":"turn_1","itemId":"msg_1","delta":"the diff"}),
+            ),
+        ]);
+        assert_eq!(transcript.items.len(), 1);
+        match transcript.item("msg_1").unwrap() {
+            TranscriptItem::AgentMessage {
+                text, completed, ..
+            } => {
+                assert_eq!(text, "Looking at the diff");
+                assert!(!*completed);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn deltas_fill_items_the_completion_leaves_empty() {
+        let transcript = transcript_of(&[
+            event(
+                "item/reasoning/summaryTextDelta",
+                json!({"turnId":"turn_1","itemId":"rs_1","delta":"Checking "}),
+            ),
+            event(
+                "item/reasoning/summaryTextDelta",
+                json!({"turnId":"turn_1","itemId":"rs_1","delta":"bounds"}),
+            ),
+            event(
+                "item/completed",
+                json!({"turnId":"turn_1","item":{"id":"rs_1","type":"reasoning","summary":[],"content":[]}}),
+            ),
+            event(
+                "item/commandExecution/outputDelta",
+                json!({"turnId":"turn_1","itemId":"cmd_1","delta":"ok\n"}),
+            ),
+            event(
+                "item/completed",
+                json!({"turnId":"turn_1","item":{"id":"cmd_1","type":"commandExecution","command":"cargo test","exitCode":101}}),
+            ),
+        ]);
+        match transcript.item("rs_1").unwrap() {
+            TranscriptItem::Reasoning {
+                summary, completed, ..
+            } => {
+                assert_eq!(summary, "Checking bounds");
+                assert!(*completed);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        match transcript.item("cmd_1").unwrap() {
+            TranscriptItem::CommandExecution {
+                command,
+                exit_code,
+                output,
+                completed,
+                ..
+            } => {
+                assert_eq!(command, "cargo test");
+                assert_eq!(*exit_code, Some(101));
+                assert_eq!(output, "ok\n");
+                assert!(*completed);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn ignores_other_turns_and_caps_command_output() {
+        let big = "x".repeat(MAX_COMMAND_OUTPUT_BYTES + 10);
+        let transcript = transcript_of(&[
+            event(
+                "item/started",
+                json!({"turnId":"turn_2","item":{"id":"msg_9","type":"agentMessage","text":""}}),
+            ),
+            event(
+                "item/commandExecution/outputDelta",
+                json!({"turnId":"turn_1","itemId":"cmd_1","delta":big}),
+            ),
+            event(
+                "item/started",
+                json!({"turnId":"turn_1","item":{"id":"fc_1","type":"fileChange","changes":[]}}),
+            ),
+        ]);
+        assert!(transcript.item("msg_9").is_none());
+        match transcript.item("cmd_1").unwrap() {
+            TranscriptItem::CommandExecution {
+                output,
+                output_truncated,
+                ..
+            } => {
+                assert_eq!(output.len(), MAX_COMMAND_OUTPUT_BYTES);
+                assert!(*output_truncated);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        assert!(matches!(
+            transcript.item("fc_1").unwrap(),
+            TranscriptItem::Other { item_type, completed: false, .. } if item_type == "fileChange"
+        ));
+    }
+
+    #[test]
+    fn caps_agent_message_and_reasoning_deltas() {
+        let half = "é".repeat(MAX_ITEM_TEXT_BYTES / 4 + 1);
+        let delta = |method: &str, item_id: &str| {
+            event(
+                method,
+                json!({"turnId":"turn_1","itemId":item_id,"delta":half}),
+            )
+        };
+        let transcript = transcript_of(&[
+            delta("item/agentMessage/delta", "msg_1"),
+            delta("item/agentMessage/delta", "msg_1"),
+            delta("item/agentMessage/delta", "msg_1"),
+            delta("item/reasoning/textDelta", "rs_1"),
+            delta("item/reasoning/textDelta", "rs_1"),
+            delta("item/reasoning/summaryTextDelta", "rs_1"),
+        ]);
+        match transcript.item("msg_1").unwrap() {
+            TranscriptItem::AgentMessage {
+                text, truncated, ..
+            } => {
+                assert!(text.len() <= MAX_ITEM_TEXT_BYTES);
+                assert!(text.len() > MAX_ITEM_TEXT_BYTES - 2);
+                assert!(*truncated);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+        match transcript.item("rs_1").unwrap() {
+            TranscriptItem::Reasoning {
+                summary,
+                text,
+                truncated,
+                ..
+            } => {
+                assert!(text.len() <= MAX_ITEM_TEXT_BYTES);
+                assert_eq!(summary, &half);
+                assert!(*truncated);
+            }
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn serializes_items_with_snake_case_type_tags() {
+        let transcript = transcript_of(&[event(
+            "item/completed",
+            json!({"turnId":"turn_1","item":{"id":"msg_1","type":"agentMessage","text":"{}"}}),
+        )]);
+        let value = serde_json::to_value(&transcript).unwrap();
+        assert_eq!(value["items"][0]["type"], "agent_message");
+        assert_eq!(value["turn_id"], "turn_1");
+        assert!(value.get("index").is_none());
+    }
+}
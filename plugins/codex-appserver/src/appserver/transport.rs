@@ -0,0 +1,177 @@
+//! Byte transports for the app-server JSON-RPC connection.
+//!
+//! A [`Transport`] yields a reader/writer pair carrying JSONL in both directions.
+//! The client does not care whether the other end is a spawned `codex app-server`
+//! child, a long-lived server on a Unix socket or TCP port, or an in-memory test double.
+
+use std::process::Stdio;
+
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
+
+use super::config::ClientConfig;
+use super::error::AppServerError;
+
+/// Boxed read half of a transport.
+pub type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Boxed write half of a transport.
+pub type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// An established connection, split into the halves the client drives.
+pub struct TransportParts {
+    pub reader: BoxedReader,
+    pub writer: BoxedWriter,
+    /// Child process backing the connection, if any. Awaited during shutdown.
+    pub child: Option<Child>,
+    /// Diagnostic output of the server (child stderr), if any. Must be drained;
+    /// the client captures it into a [`StderrBuffer`](super::StderrBuffer).
+    pub stderr: Option<BoxedReader>,
+}
+
+/// A connection to an app server that can be split into reader/writer halves.
+pub trait Transport: Send {
+    fn into_parts(self) -> TransportParts;
+}
+
+/// Transport over the stdio of a spawned child process.
+pub struct ChildStdioTransport {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+}
+
+impl ChildStdioTransport {
+    /// Spawn `command` with piped stdin/stdout/stderr. The child is killed on drop.
+    ///
+    /// On Unix the child gets its own process group, so a terminal Ctrl-C reaches
+    /// only the client, which can then interrupt the turn and shut the server down.
+    pub fn spawn(mut command: Command) -> Result<Self, AppServerError> {
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut child = command
+            .kill_on_drop(true)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(AppServerError::Spawn)?;
+
+        let stdin = child.stdin.take().ok_or_else(|| {
+            AppServerError::Spawn(std::io::Error::other("failed to capture stdin"))
+        })?;
+        let stdout = child.stdout.take().ok_or_else(|| {
+            AppServerError::Spawn(std::io::Error::other("failed to capture stdout"))
+        })?;
+        let stderr = child.stderr.take().ok_or_else(|| {
+            AppServerError::Spawn(std::io::Error::other("failed to capture stderr"))
+        })?;
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// Spawn `codex app-server` using [`ClientConfig::from_env`] (honours `CODEX_BIN`).
+    pub fn spawn_codex() -> Result<Self, AppServerError> {
+        Self::spawn(ClientConfig::from_env().command())
+    }
+}
+
+impl Transport for ChildStdioTransport {
+    fn into_parts(self) -> TransportParts {
+        TransportParts {
+            reader: Box::new(self.stdout),
+            writer: Box::new(self.stdin),
+            child: Some(self.child),
+            stderr: Some(Box::new(self.stderr)),
+        }
+    }
+}
+
+/// Transport over a Unix domain socket to an already-running app server.
+#[cfg(unix)]
+pub struct UnixSocketTransport {
+    stream: tokio::net::UnixStream,
+}
+
+#[cfg(unix)]
+impl UnixSocketTransport {
+    pub async fn connect(path: impl AsRef<std::path::Path>) -> Result<Self, AppServerError> {
+        let path = path.as_ref();
+        let stream = tokio::net::UnixStream::connect(path).await.map_err(|e| {
+            AppServerError::io(format!("Failed to connect to {}", path.display()), e)
+        })?;
+        Ok(Self { stream })
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixSocketTransport {
+    fn into_parts(self) -> TransportParts {
+        let (reader, writer) = self.stream.into_split();
+        TransportParts {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: None,
+            stderr: None,
+        }
+    }
+}
+
+/// Transport over a TCP connection to an already-running app server.
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, AppServerError> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .map_err(|e| AppServerError::io("Failed to connect over TCP", e))?;
+        Ok(Self { stream })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn into_parts(self) -> TransportParts {
+        let (reader, writer) = self.stream.into_split();
+        TransportParts {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: None,
+            stderr: None,
+        }
+    }
+}
+
+/// In-memory transport backed by a duplex pipe, for tests and embedded servers.
+pub struct DuplexTransport {
+    stream: DuplexStream,
+}
+
+impl DuplexTransport {
+    /// Create a connected pair: the transport for the client and the server-side stream.
+    /// `max_buf_size` bounds each direction's in-flight bytes.
+    pub fn pair(max_buf_size: usize) -> (Self, DuplexStream) {
+        let (client, server) = tokio::io::duplex(max_buf_size);
+        (Self { stream: client }, server)
+    }
+}
+
+impl Transport for DuplexTransport {
+    fn into_parts(self) -> TransportParts {
+        let (reader, writer) = tokio::io::split(self.stream);
+        TransportParts {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            child: None,
+            stderr: None,
+        }
+    }
+}
@@ -0,0 +1,378 @@
+//! Token usage per turn and its estimated cost.
+//!
+//! The server reports a thread's running token totals in
+//! `thread/tokenUsage/updated`. [`TurnUsage`] turns those into the usage of one
+//! turn, [`PriceTable`] prices it per model, and [`UsageRecord`]s can be appended
+//! to a JSONL ledger to follow spend over time.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::AppServerError;
+use super::events::{ServerEvent, TokenUsage};
+
+impl TokenUsage {
+    /// Field-wise `self - other`, floored at zero.
+    pub fn saturating_sub(&self, other: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens.saturating_sub(other.input_tokens),
+            cached_input_tokens: self
+                .cached_input_tokens
+                .saturating_sub(other.cached_input_tokens),
+            output_tokens: self.output_tokens.saturating_sub(other.output_tokens),
+            reasoning_output_tokens: self
+                .reasoning_output_tokens
+                .saturating_sub(other.reasoning_output_tokens),
+            total_tokens: self.total_tokens.saturating_sub(other.total_tokens),
+        }
+    }
+}
+
+/// Token usage of one turn, from the thread totals reported while it runs.
+///
+/// The first report of the turn fixes the baseline: the thread's total before the
+/// turn's first model call. Later reports replace the running total, so repeated
+/// or out-of-order updates are not double counted.
+#[derive(Debug, Clone, Default)]
+pub struct TurnUsage {
+    thread_id: String,
+    turn_id: String,
+    baseline: Option<TokenUsage>,
+    latest: TokenUsage,
+}
+
+impl TurnUsage {
+    pub fn new(thread_id: impl Into<String>, turn_id: impl Into<String>) -> Self {
+        Self {
+            thread_id: thread_id.into(),
+            turn_id: turn_id.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Fold one event in; returns whether it was a usage update for this turn.
+    pub fn apply(&mut self, event: &ServerEvent) -> bool {
+        let ServerEvent::TokenUsage {
+            thread_id,
+            turn_id,
+            usage,
+        } = event
+        else {
+            return false;
+        };
+        if thread_id.as_deref().is_some_and(|id| id != self.thread_id)
+            || turn_id.as_deref().is_some_and(|id| id != self.turn_id)
+        {
+            return false;
+        }
+        if self.baseline.is_none() {
+            self.baseline = Some(usage.total.saturating_sub(&usage.last));
+        }
+        self.latest = usage.total;
+        true
+    }
+
+    /// Tokens used by the turn so far.
+    pub fn usage(&self) -> TokenUsage {
+        match &self.baseline {
+            Some(baseline) => self.latest.saturating_sub(baseline),
+            None => TokenUsage::default(),
+        }
+    }
+}
+
+/// Prices of one model, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    /// Price of input tokens served from the prompt cache.
+    pub cached_input: f64,
+    /// Price of output tokens, reasoning included.
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub const fn new(input: f64, cached_input: f64, output: f64) -> Self {
+        Self {
+            input,
+            cached_input,
+            output,
+        }
+    }
+
+    /// Estimated cost of `usage` in US dollars. Cached input tokens are part of
+    /// `input_tokens` and reasoning tokens part of `output_tokens`, as the server
+    /// reports them.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage.cached_input_tokens.min(usage.input_tokens);
+        let uncached = usage.input_tokens - cached;
+        (uncached as f64 * self.input
+            + cached as f64 * self.cached_input
+            + usage.output_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Per-model prices, used to estimate what a turn cost.
+///
+/// A model matches its own entry, or else the longest entry it extends with a
+/// `-` suffix (`gpt-5-codex-2025-09-15` uses `gpt-5-codex`). Models without an
+/// entry have no estimate.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PriceTable {
+    models: HashMap<String, ModelPrice>,
+}
+
+impl PriceTable {
+    /// An empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Published list prices of the models codex commonly runs. They go stale;
+    /// override them with [`PriceTable::merge`] or a price file.
+    pub fn builtin() -> Self {
+        Self::new()
+            .price("gpt-5", ModelPrice::new(1.25, 0.125, 10.0))
+            .price("gpt-5-codex", ModelPrice::new(1.25, 0.125, 10.0))
+            .price("gpt-5-mini", ModelPrice::new(0.25, 0.025, 2.0))
+            .price("gpt-5-nano", ModelPrice::new(0.05, 0.005, 0.4))
+            .price("gpt-5.1", ModelPrice::new(1.25, 0.125, 10.0))
+            .price("gpt-5.1-codex", ModelPrice::new(1.25, 0.125, 10.0))
+            .price("gpt-5.4", ModelPrice::new(2.5, 0.25, 15.0))
+    }
+
+    /// Read a JSON price file: `{"<model>": {"input": 1.25, "cached_input": 0.125, "output": 10.0}}`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AppServerError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppServerError::io(format!("Failed to read {}", path.display()), e))?;
+        serde_json::from_str(&text)
+            .map_err(|e| AppServerError::json(format!("Invalid price table {}", path.display()), e))
+    }
+
+    /// Set the price of `model`.
+    pub fn price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.models.insert(model.into(), price);
+        self
+    }
+
+    /// Entries of `other` added to (and replacing those of) this table.
+    pub fn merge(mut self, other: PriceTable) -> Self {
+        self.models.extend(other.models);
+        self
+    }
+
+    /// Price of `model`, if known.
+    pub fn lookup(&self, model: &str) -> Option<ModelPrice> {
+        if let Some(price) = self.models.get(model) {
+            return Some(*price);
+        }
+        self.models
+            .iter()
+            .filter(|(name, _)| {
+                model
+                    .strip_prefix(name.as_str())
+                    .is_some_and(|rest| rest.starts_with('-'))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| *price)
+    }
+
+    /// Estimated cost of `usage` on `model`, if the model has a price.
+    pub fn cost(&self, model: &str, usage: &TokenUsage) -> Option<f64> {
+        self.lookup(model).map(|price| price.cost(usage))
+    }
+}
+
+/// One line of the usage ledger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    /// Milliseconds since the Unix epoch when the record was written.
+    pub ts_ms: u64,
+    pub session: String,
+    pub model: String,
+    /// How the run ended, e.g. `completed`, `failed`, `interrupted`.
+    pub outcome: String,
+    pub usage: TokenUsage,
+    /// Estimated cost in US dollars; `None` when the model has no price.
+    pub cost_usd: Option<f64>,
+}
+
+impl UsageRecord {
+    /// A record stamped with the current time.
+    pub fn new(
+        session: impl Into<String>,
+        model: impl Into<String>,
+        outcome: impl Into<String>,
+        usage: TokenUsage,
+        cost_usd: Option<f64>,
+    ) -> Self {
+        Self {
+            ts_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0),
+            session: session.into(),
+            model: model.into(),
+            outcome: outcome.into(),
+            usage,
+            cost_usd,
+        }
+    }
+}
+
+/// Append `record` to the JSONL ledger at `path`, creating it and its parent
+/// directories. Each record is written with a single `write_all`.
+pub fn append_usage_record(
+    path: impl AsRef<Path>,
+    record: &UsageRecord,
+) -> Result<(), AppServerError> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppServerError::io(format!("Failed to create {}", parent.display()), e))?;
+    }
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| AppServerError::io(format!("Failed to append to {}", path.display()), e))
+}
+
+/// Read every record of a usage ledger. Blank lines are skipped.
+pub fn read_usage_ledger(path: impl AsRef<Path>) -> Result<Vec<UsageRecord>, AppServerError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| AppServerError::io(format!("Failed to read {}", path.display()), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                AppServerError::json(
+                    format!("Invalid usage record at {}:{}", path.display(), i + 1),
+                    e,
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn usage_event(turn: &str, total: (u64, u64, u64), last: (u64, u64, u64)) -> ServerEvent {
+        let usage = |(input, cached, output): (u64, u64, u64)| json!({"inputTokens": input, "cachedInputTokens": cached, "outputTokens": output, "totalTokens": input + output});
+        ServerEvent::from_notification(
+            "thread/tokenUsage/updated",
+            &json!({"threadId": "thr_1", "turnId": turn, "tokenUsage": {"total": usage(total), "last": usage(last)}}),
+        )
+    }
+
+    #[test]
+    fn turn_usage_is_measured_from_the_first_report() {
+        let mut turn = TurnUsage::new("thr_1", "turn_2");
+        // Earlier turn of the same thread: ignored.
+        assert!(!turn.apply(&usage_event("turn_1", (500, 0, 50), (500, 0, 50))));
+        assert!(turn.apply(&usage_event("turn_2", (1500, 400, 150), (1000, 400, 100))));
+        assert!(turn.apply(&usage_event("turn_2", (3000, 1200, 300), (1500, 800, 150))));
+        // A repeated update changes nothing.
+        turn.apply(&usage_event("turn_2", (3000, 1200, 300), (1500, 800, 150)));
+
+        let usage = turn.usage();
+        assert_eq!(usage.input_tokens, 2500);
+        assert_eq!(usage.cached_input_tokens, 1200);
+        assert_eq!(usage.output_tokens, 250);
+        assert_eq!(usage.total_tokens, 2750);
+    }
+
+    #[test]
+    fn cost_prices_cached_input_separately() {
+        let price = ModelPrice::new(1.25, 0.125, 10.0);
+        let usage = TokenUsage {
+            input_tokens: 1_000_000,
+            cached_input_tokens: 400_000,
+            output_tokens: 100_000,
+            ..TokenUsage::default()
+        };
+        let cost = price.cost(&usage);
+        assert!((cost - (0.75 + 0.05 + 1.0)).abs() < 1e-9, "{cost}");
+    }
+
+    #[test]
+    fn lookup_matches_exact_or_dash_suffixed_names() {
+        let table = PriceTable::builtin();
+        assert_eq!(
+            table.lookup("gpt-5-codex"),
+            table.lookup("gpt-5-codex-2025-09-15")
+        );
+        assert_eq!(table.lookup("gpt-5-mini-2025-08-07").unwrap().output, 2.0);
+        // The default model has its own price; `gpt-5.4` is not a dated `gpt-5`.
+        let default_model = table.lookup("gpt-5.4").unwrap();
+        assert_ne!(Some(default_model), table.lookup("gpt-5"));
+        assert_eq!(default_model.output, 15.0);
+
+        let table = table.merge(
+            serde_json::from_value(
+                json!({"gpt-5.4": {"input": 2.0, "cached_input": 0.2, "output": 16.0}}),
+            )
+            .unwrap(),
+        );
+        assert_eq!(table.lookup("gpt-5.4").unwrap().input, 2.0);
+    }
+
+    #[test]
+    fn ledger_appends_and_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache/usage.jsonl");
+        let record = |session: &str, cost| UsageRecord {
+            ts_ms: 1,
+            session: session.to_string(),
+            model: "gpt-5".to_string(),
+            outcome: "completed".to_string(),
+            usage: TokenUsage {
+                input_tokens: 10,
+                ..TokenUsage::default()
+            },
+            cost_usd: cost,
+        };
+        append_usage_record(&path, &record("a", Some(0.5))).unwrap();
+        append_usage_record(&path, &record("b", None)).unwrap();
+
+        let records = read_usage_ledger(&path).unwrap();
+        assert_eq!(records, vec![record("a", Some(0.5)), record("b", None)]);
+    }
+
+    #[test]
+    fn malformed_price_table_is_a_json_error_naming_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("prices.json");
+        std::fs::write(&path, "{not json").unwrap();
+
+        let err = PriceTable::from_file(&path).unwrap_err();
+        assert!(matches!(err, AppServerError::Json { .. }), "{err:?}");
+        assert!(err.to_string().contains("prices.json"), "{err}");
+    }
+
+    #[test]
+    fn malformed_ledger_line_is_a_json_error_naming_the_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.jsonl");
+        std::fs::write(&path, "\n{not json\n").unwrap();
+
+        let err = read_usage_ledger(&path).unwrap_err();
+        assert!(matches!(err, AppServerError::Json { .. }), "{err:?}");
+        assert!(err.to_string().contains("usage.jsonl:2"), "{err}");
+    }
+}
@@ -0,0 +1,187 @@
+//! Server version detection and the features this client relies on.
+//!
+//! `initialize` tells us which codex we are talking to, either as `serverInfo`
+//! or embedded in the user agent (`codex_cli_rs/0.46.0 (Mac OS 15.1; arm64) ...`).
+//! [`ServerFeatures::negotiate`] turns that into a yes/no per feature, refusing
+//! servers older than [`MIN_CODEX_VERSION`].
+
+use serde::{Deserialize, Serialize};
+
+use super::error::AppServerError;
+use super::protocol::InitializeResponse;
+
+/// Oldest codex whose app server speaks the thread/turn API used here.
+pub const MIN_CODEX_VERSION: Version = Version::new(0, 46, 0);
+
+/// Oldest codex that honours `outputSchema` on `turn/start`.
+pub const OUTPUT_SCHEMA_MIN_VERSION: Version = Version::new(0, 47, 0);
+
+/// A `major.minor.patch` version. Pre-release and build suffixes are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse `1.2.3`, `v1.2.3`, `1.2` or `1.2.3-alpha.1`. Returns `None` otherwise.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().trim_start_matches('v');
+        let core = s.split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+        let major = parts.next()??;
+        let minor = parts.next()??;
+        let patch = match parts.next() {
+            Some(p) => p?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+
+    /// Extract the version from a user agent such as `codex_cli_rs/0.46.0 (...)`.
+    pub fn from_user_agent(user_agent: &str) -> Option<Self> {
+        let product = user_agent.split_whitespace().next()?;
+        let (_, version) = product.split_once('/')?;
+        Self::parse(version)
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl InitializeResponse {
+    /// The server's version, from `serverInfo` or else the user agent.
+    pub fn server_version(&self) -> Option<Version> {
+        self.server_info
+            .as_ref()
+            .and_then(|info| Version::parse(&info.version))
+            .or_else(|| {
+                self.user_agent
+                    .as_deref()
+                    .and_then(Version::from_user_agent)
+            })
+    }
+}
+
+/// Optional protocol features available on the connected server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerFeatures {
+    /// The server's version, if it reported one we could parse.
+    pub version: Option<Version>,
+    /// `turn/start` accepts `outputSchema`.
+    pub output_schema: bool,
+}
+
+impl ServerFeatures {
+    /// Decide what the server supports from its `initialize` result.
+    ///
+    /// Servers older than [`MIN_CODEX_VERSION`] are refused with
+    /// [`AppServerError::UnsupportedServer`]. A server whose version cannot be
+    /// determined is assumed to be current, so new version formats never lock us out.
+    /// An explicit `capabilities.outputSchema` from the server wins over the version.
+    pub fn negotiate(init: &InitializeResponse) -> Result<Self, AppServerError> {
+        let version = init.server_version();
+        if let Some(version) = version {
+            if version < MIN_CODEX_VERSION {
+                return Err(AppServerError::UnsupportedServer {
+                    version,
+                    minimum: MIN_CODEX_VERSION,
+                });
+            }
+        }
+        let output_schema = init
+            .capabilities
+            .get("outputSchema")
+            .and_then(|v| v.as_bool())
+            .unwrap_or_else(|| version.is_none_or(|v| v >= OUTPUT_SCHEMA_MIN_VERSION));
+        Ok(Self {
+            version,
+            output_schema,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn init(value: serde_json::Value) -> InitializeResponse {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn parses_versions_and_rejects_garbage() {
+        assert_eq!(Version::parse("0.46.0"), Some(Version::new(0, 46, 0)));
+        assert_eq!(Version::parse("v1.2"), Some(Version::new(1, 2, 0)));
+        assert_eq!(
+            Version::parse("0.47.0-alpha.3"),
+            Some(Version::new(0, 47, 0))
+        );
+        assert_eq!(Version::parse("dev"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert!(Version::new(0, 9, 0) < Version::new(0, 10, 0));
+    }
+
+    #[test]
+    fn version_from_user_agent() {
+        assert_eq!(
+            Version::from_user_agent("codex_cli_rs/0.46.0 (Mac OS 15.1.0; arm64) iTerm.app/3.5"),
+            Some(Version::new(0, 46, 0))
+        );
+        assert_eq!(Version::from_user_agent("codex"), None);
+    }
+
+    #[test]
+    fn server_info_wins_over_user_agent() {
+        let resp = init(json!({
+            "userAgent": "codex_cli_rs/0.46.0",
+            "serverInfo": {"name": "codex-app-server", "version": "0.50.1"}
+        }));
+        assert_eq!(resp.server_version(), Some(Version::new(0, 50, 1)));
+    }
+
+    #[test]
+    fn negotiate_refuses_old_and_degrades_between() {
+        let err = ServerFeatures::negotiate(&init(json!({"userAgent": "codex_cli_rs/0.40.2"})))
+            .unwrap_err();
+        assert!(matches!(err, AppServerError::UnsupportedServer { .. }));
+
+        let old =
+            ServerFeatures::negotiate(&init(json!({"userAgent": "codex_cli_rs/0.46.0"}))).unwrap();
+        assert!(!old.output_schema);
+
+        let current =
+            ServerFeatures::negotiate(&init(json!({"userAgent": "codex_cli_rs/0.52.0"}))).unwrap();
+        assert!(current.output_schema);
+    }
+
+    #[test]
+    fn negotiate_unknown_version_assumes_current_and_capabilities_override() {
+        let unknown = ServerFeatures::negotiate(&init(json!({}))).unwrap();
+        assert_eq!(unknown.version, None);
+        assert!(unknown.output_schema);
+
+        let declined = ServerFeatures::negotiate(&init(json!({
+            "userAgent": "codex_cli_rs/0.52.0",
+            "capabilities": {"outputSchema": false}
+        })))
+        .unwrap();
+        assert!(!declined.output_schema);
+    }
+}
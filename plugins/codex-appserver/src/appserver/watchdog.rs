@@ -0,0 +1,144 @@
+//! Inactivity watchdog for long-running turns.
+//!
+//! A turn's overall deadline is generous (an hour by default), so a server that
+//! hangs without exiting would otherwise look like a slow review until then. A
+//! healthy server keeps sending notifications while it works: deltas, item
+//! updates, token usage. [`StallWatchdog`] tracks the time since the last one,
+//! reporting a warning after one threshold and a stall after a second.
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// What the watchdog has to report at a given moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallCheck {
+    /// Nothing to report.
+    Active,
+    /// Quiet for longer than the warning threshold. Reported once per silence.
+    Warn(Duration),
+    /// Quiet for longer than the stall threshold; the caller should give up.
+    Stalled(Duration),
+}
+
+/// Tracks the time since the server was last heard from.
+///
+/// Call [`activity`](Self::activity) for every message received and
+/// [`check`](Self::check) once [`next_deadline`](Self::next_deadline) has passed.
+/// Either threshold may be disabled.
+#[derive(Debug, Clone)]
+pub struct StallWatchdog {
+    warn_after: Option<Duration>,
+    stall_after: Option<Duration>,
+    last_activity: Instant,
+    warned: bool,
+}
+
+impl StallWatchdog {
+    /// Start watching now. A zero threshold is treated as disabled.
+    pub fn new(warn_after: Option<Duration>, stall_after: Option<Duration>) -> Self {
+        let enabled = |d: Option<Duration>| d.filter(|d| !d.is_zero());
+        Self {
+            warn_after: enabled(warn_after),
+            stall_after: enabled(stall_after),
+            last_activity: Instant::now(),
+            warned: false,
+        }
+    }
+
+    /// Record that the server sent something.
+    pub fn activity(&mut self) {
+        self.last_activity = Instant::now();
+        self.warned = false;
+    }
+
+    /// Time since the server was last heard from.
+    pub fn idle(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
+
+    /// When [`check`](Self::check) next has something to report, or `None` if
+    /// nothing is left to watch for.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let warn = self.warn_after.filter(|_| !self.warned);
+        [warn, self.stall_after]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|after| self.last_activity + after)
+    }
+
+    /// Report on the current silence.
+    pub fn check(&mut self) -> StallCheck {
+        let idle = self.idle();
+        if self.stall_after.is_some_and(|after| idle >= after) {
+            return StallCheck::Stalled(idle);
+        }
+        if !self.warned && self.warn_after.is_some_and(|after| idle >= after) {
+            self.warned = true;
+            return StallCheck::Warn(idle);
+        }
+        StallCheck::Active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pretend nothing arrived for `ms`. Kept to milliseconds: `Instant` cannot go
+    /// back past the host's boot, and fresh CI machines have little uptime.
+    fn quiet_for(dog: &mut StallWatchdog, ms: u64) {
+        dog.last_activity = Instant::now()
+            .checked_sub(Duration::from_millis(ms))
+            .expect("host uptime shorter than the test's silence");
+    }
+
+    #[test]
+    fn warns_once_then_reports_stall() {
+        let mut dog = StallWatchdog::new(
+            Some(Duration::from_millis(100)),
+            Some(Duration::from_millis(300)),
+        );
+        assert_eq!(dog.check(), StallCheck::Active);
+        assert_eq!(
+            dog.next_deadline(),
+            Some(dog.last_activity + Duration::from_millis(100))
+        );
+
+        quiet_for(&mut dog, 110);
+        assert!(matches!(dog.check(), StallCheck::Warn(_)));
+        assert_eq!(dog.check(), StallCheck::Active);
+        assert_eq!(
+            dog.next_deadline(),
+            Some(dog.last_activity + Duration::from_millis(300))
+        );
+
+        quiet_for(&mut dog, 310);
+        assert!(
+            matches!(dog.check(), StallCheck::Stalled(idle) if idle >= Duration::from_millis(300))
+        );
+    }
+
+    #[test]
+    fn activity_resets_the_silence() {
+        let mut dog = StallWatchdog::new(Some(Duration::from_millis(100)), None);
+        quiet_for(&mut dog, 110);
+        assert!(matches!(dog.check(), StallCheck::Warn(_)));
+        assert_eq!(dog.next_deadline(), None);
+
+        dog.activity();
+        assert!(dog.idle() < Duration::from_millis(100));
+        assert_eq!(dog.check(), StallCheck::Active);
+        quiet_for(&mut dog, 110);
+        assert!(matches!(dog.check(), StallCheck::Warn(_)));
+    }
+
+    #[test]
+    fn zero_thresholds_disable_the_watchdog() {
+        let mut dog = StallWatchdog::new(Some(Duration::ZERO), None);
+        assert_eq!(dog.next_deadline(), None);
+        quiet_for(&mut dog, 1000);
+        assert_eq!(dog.check(), StallCheck::Active);
+    }
+}
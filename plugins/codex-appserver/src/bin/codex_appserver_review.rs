@@ -2,18 +2,156 @@
 //!
 //! Usage:
 //!   codex-appserver-review --project-path <path> --model <model> <session-name> <prompt-file>
+//!
+//! Options:
+//!   --record <file>  append the JSONL exchanged with the app server to a transcript
+//!   --replay <file>  replay a recorded transcript instead of spawning the app server
+//!   --max-tokens <n>   interrupt the turn once it has used n tokens (input + output)
+//!   --max-cost <usd>   interrupt the turn once its estimated cost exceeds this many dollars
+//!   --format <list>    also write the review in these formats, comma-separated or
+//!                      repeated: `sarif` (<session>.sarif), `junit` (<session>.junit.xml),
+//!                      `checkstyle` (<session>.checkstyle.xml), `codequality` (GitLab
+//!                      Code Quality, <session>.codequality.json), `rdjson` and `rdjsonl`
+//!                      (reviewdog, <session>.rdjson / <session>.rdjsonl). A standalone
+//!                      <session>.html with filters and source snippets is always written.
+//!   --junit-fail-on <severity>  least severe finding that fails a JUnit test case:
+//!                      critical, high (default), medium or low
+//!   --progress=<mode>  how to show the turn's progress on stderr: `rich` (a live status
+//!                      line; the default on a terminal), `plain` (one line per command
+//!                      or file read, plus a heartbeat; the default otherwise) or `quiet`
+//!
+//! Exit codes:
+//!   0  review completed
+//!   1  review failed (turn failed, unparseable output, I/O error)
+//!   2  usage error
+//!   3  timed out waiting for the app server
+//!   4  app server returned a JSON-RPC error
+//!   5  app server exited or could not be spawned
+//!   6  installed codex is older than this tool supports
+//!   7  stopped by --max-tokens/--max-cost (partial output and report saved to the cache)
+//!   8  app server went silent for CODEX_STALL_TIMEOUT (partial output saved to the cache)
+//!   130/143  interrupted by SIGINT/SIGTERM (partial output saved to the cache)
+//!
+//! Environment:
+//!   CODEX_TURN_TIMEOUT  seconds to wait for the review turn (default 3600, 0 = no limit)
+//!   CODEX_TURN_RETRIES  times to restart a crashed app server and retry the turn (default 1)
+//!   CODEX_STALL_WARN    seconds without any message from the app server before warning
+//!                       that the turn may be stuck (default 300, 0 = never)
+//!   CODEX_STALL_TIMEOUT seconds without any message before interrupting the turn as
+//!                       stalled (default 0 = never)
+//!   CODEX_PRICE_TABLE   JSON file of model prices (USD per 1M tokens) overriding the built-in ones
+//!
+//! Token usage and estimated cost of each run are appended to
+//! `<project>/.codex-review-cache/usage.jsonl`.
 
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use codex_appserver::appserver::protocol::{
+    review_output_schema, ApprovalPolicy, InitializeParams, ReviewOutput, SandboxMode, Severity,
+    ThreadStartParams, TurnCompletedParams, TurnStartParams, TurnStatus,
+};
+use codex_appserver::appserver::report;
+use codex_appserver::appserver::stderr::ERROR_STDERR_TAIL_LINES;
+use codex_appserver::appserver::{
+    append_usage_record, read_transcript, AppServerError, ClientConfig, CodexAppServerClient,
+    EventStream, ModelPrice, PriceTable, ReplayTransport, ReportFormat, ReportOptions,
+    ServerEvent, ServerFeatures, StallCheck, StallWatchdog, SupervisedClient, TokenUsage,
+    TranscriptItem, TurnTranscript, TurnUsage, UsageRecord,
+};
+use serde::Serialize;
+
+/// Top-level failure of a review run, mapped to a process exit code.
+#[derive(Debug)]
+enum ReviewError {
+    Usage(String),
+    AppServer(AppServerError),
+    Failed(String),
+    /// Stopped by a signal; carries the signal name.
+    Interrupted(&'static str),
+    /// Stopped because the turn went over its token or cost budget.
+    BudgetExceeded(BudgetHit),
+}
+
+impl ReviewError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            ReviewError::Failed(_) => 1,
+            ReviewError::Usage(_) => 2,
+            ReviewError::Interrupted("SIGTERM") => 143,
+            ReviewError::Interrupted(_) => 130,
+            ReviewError::BudgetExceeded(_) => 7,
+            ReviewError::AppServer(e) => match e.root() {
+                AppServerError::Timeout { .. } => 3,
+                AppServerError::Rpc { .. } => 4,
+                AppServerError::ServerExited { .. } | AppServerError::Spawn(_) => 5,
+                AppServerError::UnsupportedServer { .. } => 6,
+                AppServerError::Stalled { .. } => 8,
+                _ => 1,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for ReviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReviewError::Usage(msg) | ReviewError::Failed(msg) => write!(f, "{msg}"),
+            ReviewError::AppServer(e) => write!(f, "{e}"),
+            ReviewError::Interrupted(signal) => write!(f, "Review interrupted by {signal}"),
+            ReviewError::BudgetExceeded(hit) => write!(f, "Review stopped: {hit}"),
+        }
+    }
+}
+
+impl From<AppServerError> for ReviewError {
+    fn from(e: AppServerError) -> Self {
+        ReviewError::AppServer(e)
+    }
+}
+
+impl From<String> for ReviewError {
+    fn from(msg: String) -> Self {
+        ReviewError::Failed(msg)
+    }
+}
 
-use codex_appserver::appserver::protocol::{review_output_schema, ReviewOutput, Severity};
-use codex_appserver::appserver::CodexAppServerClient;
-use serde_json::{json, Value};
+const USAGE: &str = "Usage: codex-appserver-review --project-path <path> [--model <model>] \
+                     [--record <file>] [--replay <file>] [--max-tokens <n>] [--max-cost <usd>] \
+                     [--format <fmt>[,<fmt>...]] [--junit-fail-on <severity>] \
+                     [--progress=plain|quiet|rich] \
+                     <session-name> <prompt-file>";
 
-fn parse_args() -> Result<(PathBuf, String, String, PathBuf), String> {
+/// Parsed command line.
+struct CliArgs {
+    project_path: PathBuf,
+    model: String,
+    session_name: String,
+    prompt_file: PathBuf,
+    /// Transcript file to record the protocol exchange to.
+    record: Option<PathBuf>,
+    /// Transcript file to replay instead of spawning the app server.
+    replay: Option<PathBuf>,
+    max_tokens: Option<u64>,
+    /// In US dollars.
+    max_cost: Option<f64>,
+    progress: ProgressMode,
+    /// Extra report formats to write next to the JSON and Markdown.
+    formats: Vec<ReportFormat>,
+    report_options: ReportOptions,
+}
+
+fn parse_args() -> Result<CliArgs, String> {
     let args: Vec<String> = std::env::args().skip(1).collect();
     let mut project_path: Option<PathBuf> = None;
     let mut model: Option<String> = None;
+    let mut record: Option<PathBuf> = None;
+    let mut replay: Option<PathBuf> = None;
+    let mut max_tokens: Option<u64> = None;
+    let mut max_cost: Option<f64> = None;
+    let mut progress: Option<ProgressMode> = None;
+    let mut formats: Vec<ReportFormat> = Vec::new();
+    let mut report_options = ReportOptions::default();
     let mut positional: Vec<String> = Vec::new();
 
     let mut i = 0;
@@ -28,10 +166,49 @@ fn parse_args() -> Result<(PathBuf, String, String, PathBuf), String> {
                 i += 1;
                 model = Some(args.get(i).ok_or("Missing --model value")?.clone());
             }
+            "--record" => {
+                i += 1;
+                record = Some(PathBuf::from(args.get(i).ok_or("Missing --record value")?));
+            }
+            "--replay" => {
+                i += 1;
+                replay = Some(PathBuf::from(args.get(i).ok_or("Missing --replay value")?));
+            }
+            "--max-tokens" => {
+                i += 1;
+                let value = args.get(i).ok_or("Missing --max-tokens value")?;
+                max_tokens = Some(parse_budget_value("--max-tokens", value)?);
+            }
+            "--max-cost" => {
+                i += 1;
+                let value = args.get(i).ok_or("Missing --max-cost value")?;
+                max_cost = Some(parse_budget_value("--max-cost", value)?);
+            }
+            "--format" => {
+                i += 1;
+                let value = args.get(i).ok_or("Missing --format value")?;
+                add_formats(&mut formats, value)?;
+            }
+            arg if arg.starts_with("--format=") => {
+                add_formats(&mut formats, &arg["--format=".len()..])?;
+            }
+            "--junit-fail-on" => {
+                i += 1;
+                let value = args.get(i).ok_or("Missing --junit-fail-on value")?;
+                report_options.fail_on = Severity::parse(value).ok_or_else(|| {
+                    format!("--junit-fail-on must be critical, high, medium or low, got {value:?}")
+                })?;
+            }
+            "--progress" => {
+                i += 1;
+                let value = args.get(i).ok_or("Missing --progress value")?;
+                progress = Some(ProgressMode::parse(value)?);
+            }
+            arg if arg.starts_with("--progress=") => {
+                progress = Some(ProgressMode::parse(&arg["--progress=".len()..])?);
+            }
             "--help" | "-h" => {
-                eprintln!(
-                    "Usage: codex-appserver-review --project-path <path> [--model <model>] <session-name> <prompt-file>"
-                );
+                eprintln!("{USAGE}");
                 std::process::exit(0);
             }
             _ => {
@@ -52,12 +229,14 @@ fn parse_args() -> Result<(PathBuf, String, String, PathBuf), String> {
 
     if positional.len() != 2 {
         return Err(format!(
-            "Usage: codex-appserver-review --project-path <path> [--model <model>] <session-name> <prompt-file>\n\
-             Got {} positional args: {:?}",
+            "{USAGE}\nGot {} positional args: {:?}",
             positional.len(),
             positional
         ));
     }
+    if record.is_some() && replay.is_some() {
+        return Err("--record and --replay cannot be combined".to_string());
+    }
 
     let session_name = positional[0].clone();
     let prompt_file = PathBuf::from(&positional[1]);
@@ -69,149 +248,777 @@ fn parse_args() -> Result<(PathBuf, String, String, PathBuf), String> {
         ));
     }
 
-    Ok((project_path, model, session_name, prompt_file))
+    Ok(CliArgs {
+        project_path,
+        model,
+        session_name,
+        prompt_file,
+        record,
+        replay,
+        max_tokens,
+        max_cost,
+        progress: progress.unwrap_or_else(ProgressMode::detect),
+        formats,
+        report_options,
+    })
+}
+
+/// Add the formats named in `value` to `formats`, skipping repeats.
+fn add_formats(formats: &mut Vec<ReportFormat>, value: &str) -> Result<(), String> {
+    for format in parse_formats(value)? {
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a comma-separated list of report format names.
+fn parse_formats(value: &str) -> Result<Vec<ReportFormat>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            ReportFormat::parse(name).ok_or_else(|| {
+                let known: Vec<_> = ReportFormat::ALL.iter().map(|f| f.name()).collect();
+                format!("Unknown --format {name:?}; expected one of: {}", known.join(", "))
+            })
+        })
+        .collect()
+}
+
+/// Parse a budget flag's value, which must be a positive number.
+fn parse_budget_value<T>(flag: &str, value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+{
+    match value.parse::<T>() {
+        Ok(v) if v > T::default() => Ok(v),
+        _ => Err(format!("{flag} must be a positive number, got {value:?}")),
+    }
 }
 
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
         eprintln!("Error: {e}");
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
     }
 }
 
-async fn run() -> Result<(), String> {
-    let (project_path, model, session_name, prompt_file) = parse_args()?;
+async fn run() -> Result<(), ReviewError> {
+    let CliArgs {
+        project_path,
+        model,
+        session_name,
+        prompt_file,
+        record,
+        replay,
+        max_tokens,
+        max_cost,
+        progress,
+        formats,
+        report_options,
+    } = parse_args().map_err(ReviewError::Usage)?;
 
     let prompt = std::fs::read_to_string(&prompt_file)
         .map_err(|e| format!("Failed to read prompt file {}: {e}", prompt_file.display()))?;
 
     if prompt.trim().is_empty() {
-        return Err("Prompt file is empty".to_string());
-    }
-
-    eprintln!("Spawning codex app-server...");
-    let mut client = CodexAppServerClient::spawn().await?;
-
-    // 1. Initialize handshake
-    eprintln!("Initializing...");
-    client
-        .request(
-            "initialize",
-            json!({
-                "clientInfo": {
-                    "name": "codex-appserver-review",
-                    "version": "0.1.0"
-                },
-                "capabilities": {}
-            }),
-        )
-        .await?;
+        return Err(ReviewError::Usage("Prompt file is empty".to_string()));
+    }
+
+    let cache_dir = project_path.join(".codex-review-cache/reviews");
+    let ledger = project_path.join(".codex-review-cache/usage.jsonl");
+    let prices = load_price_table();
+    let budget = Budget::new(max_tokens, max_cost, &model, &prices)?;
 
-    client.notify("initialized", Value::Null).await?;
+    // The server's stderr goes to a per-session log; its tail is attached to
+    // errors, so it is not echoed to the console as well.
+    let stderr_log = project_path
+        .join(".codex-review-cache/logs")
+        .join(format!("{session_name}.stderr.log"));
+
+    let mut config = ClientConfig::from_env()
+        .cwd(&project_path)
+        .stderr_log(&stderr_log)
+        .echo_stderr(false);
+    if let Some(path) = &record {
+        eprintln!("Recording protocol transcript to {}", path.display());
+        config = config.transcript(path);
+    }
+
+    // Take over SIGINT/SIGTERM before the server exists: it runs in its own
+    // process group, so a Ctrl-C never reaches it and would otherwise leave it
+    // running once we are gone.
+    let mut signals = ShutdownSignals::install()?;
+
+    // 1. Spawn + initialize handshake. The supervisor repeats both if the server
+    // crashes, so the turn can be retried on a fresh process.
+    let init_params = InitializeParams::new("codex-appserver-review", env!("CARGO_PKG_VERSION"));
+    let connect = async {
+        match replay {
+            Some(path) => {
+                eprintln!("Replaying protocol transcript {}...", path.display());
+                let records = read_transcript(&path)?;
+                SupervisedClient::with_connector(
+                    move || {
+                        let transport = ReplayTransport::new(records.clone());
+                        let client = CodexAppServerClient::connect_with_config(transport, &config);
+                        async move { Ok(client) }
+                    },
+                    init_params,
+                )
+                .await
+            }
+            None => {
+                eprintln!("Spawning codex app-server...");
+                SupervisedClient::spawn(config, init_params).await
+            }
+        }
+    };
+    // Interrupted mid-handshake: dropping the half-built client kills the server.
+    let supervisor = tokio::select! {
+        supervisor = connect => supervisor?,
+        signal = signals.recv() => {
+            eprintln!("\nReceived {signal} during startup, stopping app server...");
+            return Err(ReviewError::Interrupted(signal));
+        }
+    };
+    let client = supervisor.client().await;
+    let features = match negotiate_features(&client) {
+        Ok(features) => features,
+        Err(e) => {
+            shutdown_client(client).await;
+            return Err(e.into());
+        }
+    };
 
     // 2. Create thread
     eprintln!("Creating thread (model: {model}, sandbox: read-only)...");
-    let thread_result = client
-        .request(
-            "thread/start",
-            json!({
-                "model": model,
-                "cwd": project_path.to_string_lossy(),
-                "sandbox": "read-only",
-                "approvalPolicy": "never"
-            }),
-        )
-        .await?;
-
-    let thread_id = thread_result
-        .get("thread")
-        .and_then(|t| t.get("id"))
-        .or_else(|| thread_result.get("id"))
-        .and_then(|v| v.as_str())
-        .ok_or_else(|| format!("thread/start did not return a thread id"))?
-        .to_string();
+    let thread_settings = ThreadStartParams {
+        model: Some(model.clone()),
+        cwd: Some(project_path.to_string_lossy().into_owned()),
+        sandbox: Some(SandboxMode::ReadOnly),
+        approval_policy: Some(ApprovalPolicy::Never),
+    };
+    let thread_id = tokio::select! {
+        started = client.thread_start(&thread_settings) => started?.thread.id,
+        signal = signals.recv() => {
+            eprintln!("\nReceived {signal} during startup, stopping app server...");
+            shutdown_client(client).await;
+            return Err(ReviewError::Interrupted(signal));
+        }
+    };
 
     eprintln!("Thread created: {}", &thread_id[..thread_id.len().min(16)]);
 
-    // 3. Start turn with prompt + outputSchema
-    eprintln!("Starting review turn...");
-    client.clear_text().await;
-    let turn_result = client
-        .request(
-            "turn/start",
-            json!({
-                "threadId": thread_id,
-                "input": [{ "type": "text", "text": prompt }],
-                "outputSchema": review_output_schema()
-            }),
-        )
-        .await?;
-
-    // Extract turn ID for correlation
-    let turn_id = turn_result
-        .get("id")
-        .or_else(|| turn_result.get("turn").and_then(|t| t.get("id")))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-
-    // 4. Wait for matching turn/completed
-    let turn_timeout = parse_turn_timeout();
-    eprintln!("Waiting for review completion (timeout: {}s)...", turn_timeout.as_secs());
-    let completed = client
-        .wait_turn_completed(turn_id.as_deref(), turn_timeout)
-        .await?;
-
-    // 5. Check for turn-level error
-    let turn_obj = completed.get("turn");
-    let status = turn_obj
-        .and_then(|t| t.get("status"))
-        .and_then(|s| s.as_str())
-        .unwrap_or("unknown");
-
-    match status {
-        "completed" => {}
-        "interrupted" => {
-            return Err("Turn was interrupted before completion".to_string());
-        }
-        "failed" => {
-            let err_msg = turn_obj
-                .and_then(|t| t.get("error"))
-                .map(|e| {
-                    e.get("message")
-                        .and_then(|m| m.as_str())
-                        .map(|s| s.to_string())
-                        .unwrap_or_else(|| e.to_string())
-                })
+    // 3-4. Run the turn and wait for it. An interrupted review still stops the turn
+    // and keeps its partial output. If the server crashes mid-turn, restart it,
+    // resume the thread and retry.
+    let options = TurnOptions {
+        prompt: &prompt,
+        features,
+        timeout: parse_turn_timeout(),
+        stall_warn: parse_secs_env("CODEX_STALL_WARN", 300),
+        stall_timeout: parse_secs_env("CODEX_STALL_TIMEOUT", 0),
+        budget,
+        progress,
+    };
+    let max_retries = parse_turn_retries();
+    let mut retries = 0;
+    let (client, turn_id, completed, monitor) = loop {
+        let client = supervisor.client().await;
+        match run_turn(&client, &thread_id, &options, &mut signals).await {
+            Ok(TurnAttempt::Completed {
+                turn_id,
+                completed,
+                monitor,
+            }) => break (client, turn_id, completed, monitor),
+            Ok(TurnAttempt::Interrupted {
+                turn_id,
+                signal,
+                mut monitor,
+            }) => {
+                eprintln!("\nReceived {signal}, interrupting turn...");
+                abort_turn(client, &cache_dir, &session_name, &thread_id, &turn_id, &mut monitor)
+                    .await;
+                let usage = ReviewUsage::new(&model, monitor.usage.usage(), &prices);
+                record_usage(&ledger, &session_name, "interrupted", &usage);
+                return Err(ReviewError::Interrupted(signal));
+            }
+            Ok(TurnAttempt::BudgetExceeded {
+                turn_id,
+                hit,
+                mut monitor,
+            }) => {
+                eprintln!("\n{hit}, interrupting turn...");
+                let partial = abort_turn(
+                    client,
+                    &cache_dir,
+                    &session_name,
+                    &thread_id,
+                    &turn_id,
+                    &mut monitor,
+                )
+                .await;
+                let usage = ReviewUsage::new(&model, monitor.usage.usage(), &prices);
+                record_usage(&ledger, &session_name, "budget_exceeded", &usage);
+                // Salvage the review if the agent had already written it out.
+                let review = parse_last_review_output(&partial).ok();
+                save_review_json(&cache_dir, &session_name, review.as_ref(), &usage, Some(&hit))?;
+                save_review_markdown(&cache_dir, &session_name, review.as_ref(), Some(&hit))?;
+                if let Some(review) = &review {
+                    save_review_reports(
+                        &cache_dir,
+                        &session_name,
+                        review,
+                        &formats,
+                        &report_options,
+                    )?;
+                    save_review_html(&cache_dir, &session_name, review, &project_path)?;
+                }
+                return Err(ReviewError::BudgetExceeded(hit));
+            }
+            Ok(TurnAttempt::Stalled {
+                turn_id,
+                idle,
+                mut monitor,
+            }) => {
+                eprintln!(
+                    "\nNo messages from the app server for {}s, interrupting turn...",
+                    idle.as_secs()
+                );
+                let error = AppServerError::Stalled {
+                    operation: format!("turn {turn_id}"),
+                    idle,
+                    stderr_tail: client.stderr_tail(ERROR_STDERR_TAIL_LINES),
+                };
+                abort_turn(client, &cache_dir, &session_name, &thread_id, &turn_id, &mut monitor)
+                    .await;
+                let usage = ReviewUsage::new(&model, monitor.usage.usage(), &prices);
+                record_usage(&ledger, &session_name, "stalled", &usage);
+                return Err(error.into());
+            }
+            Err(e) if e.is_server_exited() && retries < max_retries => {
+                retries += 1;
+                eprintln!("{e}");
+                eprintln!("Restarting app server and retrying the turn ({retries}/{max_retries})...");
+                supervisor.reconnect().await?;
+                supervisor
+                    .resume_thread(&thread_id, &thread_settings)
+                    .await?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    // 5. Keep the record of what the reviewer did and spent, whatever the outcome.
+    if let Err(e) = save_turn_transcript(&cache_dir, &session_name, &monitor.transcript) {
+        eprintln!("Warning: failed to save turn transcript: {e}");
+    }
+    let usage = ReviewUsage::new(&model, monitor.usage.usage(), &prices);
+    let outcome = match completed.turn.status {
+        TurnStatus::Completed => "completed",
+        TurnStatus::Failed => "failed",
+        TurnStatus::Interrupted => "interrupted",
+        _ => "unknown",
+    };
+    record_usage(&ledger, &session_name, outcome, &usage);
+
+    // 6. Check for turn-level error
+    match completed.turn.status {
+        TurnStatus::Completed => {}
+        TurnStatus::Interrupted => {
+            return Err("Turn was interrupted before completion".to_string().into());
+        }
+        TurnStatus::Failed => {
+            let err_msg = completed
+                .turn
+                .error
+                .map(|e| e.message)
+                .filter(|m| !m.is_empty())
                 .unwrap_or_else(|| "unknown error".to_string());
-            return Err(format!("Turn failed: {err_msg}"));
+            return Err(format!("Turn failed: {err_msg}").into());
         }
         other => {
-            return Err(format!("Unexpected turn status: {other}"));
+            return Err(format!("Unexpected turn status: {other:?}").into());
         }
     }
 
-    // 6. Parse accumulated agent text as structured output.
-    let agent_text = client.accumulated_text().await;
+    // 7. Parse this turn's accumulated agent text as structured output.
+    let agent_text = client
+        .take_turn_output(&thread_id, &turn_id)
+        .await
+        .unwrap_or_default();
 
     if agent_text.is_empty() {
-        return Err("Agent produced no output text".to_string());
+        return Err("Agent produced no output text".to_string().into());
     }
 
     let review = parse_last_review_output(&agent_text)?;
 
-    // 7. Save to cache
-    let cache_dir = project_path.join(".codex-review-cache/reviews");
+    // 8. Save to cache
     std::fs::create_dir_all(&cache_dir)
         .map_err(|e| format!("Failed to create cache dir: {e}"))?;
 
-    save_review_json(&cache_dir, &session_name, &review)?;
-    save_review_markdown(&cache_dir, &session_name, &review)?;
+    save_review_json(&cache_dir, &session_name, Some(&review), &usage, None)?;
+    save_review_markdown(&cache_dir, &session_name, Some(&review), None)?;
+    save_review_reports(&cache_dir, &session_name, &review, &formats, &report_options)?;
+    save_review_html(&cache_dir, &session_name, &review, &project_path)?;
+
+    // 9. Print summary
+    print_summary(&session_name, &cache_dir, &review, &usage);
+
+    // 10. Shutdown
+    shutdown_client(client).await;
+
+    Ok(())
+}
+
+/// Outcome of one attempt at the review turn.
+enum TurnAttempt {
+    Completed {
+        turn_id: String,
+        completed: TurnCompletedParams,
+        monitor: TurnMonitor,
+    },
+    /// A shutdown signal arrived while the turn was running.
+    Interrupted {
+        turn_id: String,
+        signal: &'static str,
+        monitor: TurnMonitor,
+    },
+    /// The turn used more tokens or money than the budget allows.
+    BudgetExceeded {
+        turn_id: String,
+        hit: BudgetHit,
+        monitor: TurnMonitor,
+    },
+    /// The server sent nothing for longer than the stall timeout.
+    Stalled {
+        turn_id: String,
+        idle: Duration,
+        monitor: TurnMonitor,
+    },
+}
+
+/// How to run the review turn.
+struct TurnOptions<'a> {
+    prompt: &'a str,
+    features: ServerFeatures,
+    timeout: Duration,
+    /// Silence after which to warn that the turn may be stuck.
+    stall_warn: Option<Duration>,
+    /// Silence after which to give up on the turn.
+    stall_timeout: Option<Duration>,
+    budget: Budget,
+    progress: ProgressMode,
+}
+
+/// Limits on what one review may spend; unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default)]
+struct Budget {
+    max_tokens: Option<u64>,
+    max_cost: Option<f64>,
+    /// Price of the review model, needed to enforce `max_cost`.
+    price: Option<ModelPrice>,
+}
+
+impl Budget {
+    /// A cost limit can only be enforced for a model with a known price.
+    fn new(
+        max_tokens: Option<u64>,
+        max_cost: Option<f64>,
+        model: &str,
+        prices: &PriceTable,
+    ) -> Result<Self, ReviewError> {
+        let price = prices.lookup(model);
+        if max_cost.is_some() && price.is_none() {
+            return Err(ReviewError::Usage(format!(
+                "--max-cost needs a price for model {model}; add one with CODEX_PRICE_TABLE"
+            )));
+        }
+        Ok(Self {
+            max_tokens,
+            max_cost,
+            price,
+        })
+    }
+
+    /// The first limit `usage` goes over, if any.
+    fn check(&self, usage: &TokenUsage) -> Option<BudgetHit> {
+        let used_tokens = usage.total_tokens;
+        if let Some(max_tokens) = self.max_tokens.filter(|&max| used_tokens > max) {
+            return Some(BudgetHit::Tokens {
+                max_tokens,
+                used_tokens,
+            });
+        }
+        let used_cost_usd = self.price?.cost(usage);
+        self.max_cost
+            .filter(|&max| used_cost_usd > max)
+            .map(|max_cost_usd| BudgetHit::Cost {
+                max_cost_usd,
+                used_cost_usd,
+            })
+    }
+}
+
+/// Which budget a turn went over, and by how much. Saved in the session report.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "limit", rename_all = "snake_case")]
+enum BudgetHit {
+    Tokens { max_tokens: u64, used_tokens: u64 },
+    Cost { max_cost_usd: f64, used_cost_usd: f64 },
+}
+
+impl std::fmt::Display for BudgetHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetHit::Tokens {
+                max_tokens,
+                used_tokens,
+            } => write!(
+                f,
+                "token budget exceeded ({used_tokens} tokens used, limit {max_tokens})"
+            ),
+            BudgetHit::Cost {
+                max_cost_usd,
+                used_cost_usd,
+            } => write!(
+                f,
+                "cost budget exceeded (${used_cost_usd:.4} spent, limit ${max_cost_usd:.2})"
+            ),
+        }
+    }
+}
+
+/// Follows the events of the review turn while it runs.
+struct TurnMonitor {
+    events: EventStream,
+    transcript: TurnTranscript,
+    usage: TurnUsage,
+}
+
+impl TurnMonitor {
+    fn new(events: EventStream, thread_id: &str, turn_id: &str) -> Self {
+        Self {
+            events,
+            transcript: TurnTranscript::new(thread_id, turn_id),
+            usage: TurnUsage::new(thread_id, turn_id),
+        }
+    }
+
+    /// Returns whether the event updated the turn's token usage.
+    fn apply(&mut self, event: &ServerEvent) -> bool {
+        self.transcript.apply(event);
+        self.usage.apply(event)
+    }
+
+    /// Apply the events already received. Called once the turn has completed, as
+    /// its last items may still be queued behind the completion.
+    fn drain(&mut self) {
+        while let Some(event) = self.events.try_next() {
+            self.apply(&event);
+        }
+    }
+}
+
+/// Check the server's version from its `initialize` result. Servers that are too
+/// old are refused; servers without `outputSchema` support get the prompt alone.
+fn negotiate_features(client: &CodexAppServerClient) -> Result<ServerFeatures, AppServerError> {
+    let init = client.server_info().unwrap_or_default();
+    let features = ServerFeatures::negotiate(&init)?;
+    match features.version {
+        Some(version) => eprintln!("Connected to codex {version}"),
+        None => eprintln!(
+            "Warning: could not determine the codex version (user agent: {}); assuming a current release",
+            init.user_agent.as_deref().unwrap_or("none")
+        ),
+    }
+    if !features.output_schema {
+        eprintln!(
+            "Warning: this codex does not support outputSchema; relying on the prompt for JSON output"
+        );
+    }
+    Ok(features)
+}
+
+/// Start the review turn (prompt, plus outputSchema when supported) and wait for
+/// its `turn/completed`.
+async fn run_turn(
+    client: &CodexAppServerClient,
+    thread_id: &str,
+    options: &TurnOptions<'_>,
+    signals: &mut ShutdownSignals,
+) -> Result<TurnAttempt, AppServerError> {
+    eprintln!("Starting review turn...");
+    let params = TurnStartParams {
+        output_schema: options.features.output_schema.then(review_output_schema),
+        ..TurnStartParams::text(thread_id, options.prompt)
+    };
+    // Subscribe first so no item of the turn is missed.
+    let events = client.subscribe();
+    // Turn ID for correlation and per-turn output lookup
+    let turn_id = client.turn_start(&params).await?.turn.id;
+    let mut monitor = TurnMonitor::new(events, thread_id, &turn_id);
+
+    // Wait for matching turn/completed, following the turn's items meanwhile.
+    eprintln!("Waiting for review completion (timeout: {}s)...", options.timeout.as_secs());
+    let wait = client.wait_for_turn(&turn_id, options.timeout);
+    tokio::pin!(wait);
+    let mut watchdog = StallWatchdog::new(options.stall_warn, options.stall_timeout);
+    let mut progress = Progress::new(options.progress);
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    let attempt = loop {
+        tokio::select! {
+            completed = &mut wait => {
+                let completed = match completed {
+                    Ok(completed) => completed,
+                    Err(e) => {
+                        progress.finish();
+                        return Err(e);
+                    }
+                };
+                monitor.drain();
+                progress.catch_up(&monitor);
+                let turn_id = turn_id.clone();
+                break TurnAttempt::Completed { turn_id, completed, monitor };
+            }
+            signal = signals.recv() => {
+                let turn_id = turn_id.clone();
+                break TurnAttempt::Interrupted { turn_id, signal, monitor };
+            }
+            () = sleep_until(watchdog.next_deadline()) => match watchdog.check() {
+                StallCheck::Warn(idle) => progress.println(&format!(
+                    "Warning: no messages from the app server for {}s; the turn may be stuck",
+                    idle.as_secs()
+                )),
+                StallCheck::Stalled(idle) => {
+                    let turn_id = turn_id.clone();
+                    break TurnAttempt::Stalled { turn_id, idle, monitor };
+                }
+                StallCheck::Active => {}
+            },
+            _ = ticker.tick() => progress.tick(),
+            Some(event) = monitor.events.next() => {
+                watchdog.activity();
+                let usage_changed = monitor.apply(&event);
+                progress.update(&event, &monitor);
+                if usage_changed {
+                    if let Some(hit) = options.budget.check(&monitor.usage.usage()) {
+                        let turn_id = turn_id.clone();
+                        break TurnAttempt::BudgetExceeded { turn_id, hit, monitor };
+                    }
+                }
+            }
+        }
+    };
+    progress.finish();
+    Ok(attempt)
+}
+
+/// How to show the turn's progress on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgressMode {
+    /// Nothing beyond the start and end of the turn.
+    Quiet,
+    /// One line per command or file read, and a heartbeat; suits logs and CI.
+    Plain,
+    /// Plain's lines under a status line redrawn every second.
+    Rich,
+}
+
+impl ProgressMode {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "quiet" => Ok(ProgressMode::Quiet),
+            "plain" => Ok(ProgressMode::Plain),
+            "rich" => Ok(ProgressMode::Rich),
+            _ => Err(format!("--progress must be plain, quiet or rich, got {value:?}")),
+        }
+    }
+
+    /// `rich` when stderr is a terminal, `plain` otherwise.
+    fn detect() -> Self {
+        use std::io::IsTerminal;
+        if std::io::stderr().is_terminal() {
+            ProgressMode::Rich
+        } else {
+            ProgressMode::Plain
+        }
+    }
+}
+
+/// Renders what the reviewer is doing, from the turn's events.
+struct Progress {
+    mode: ProgressMode,
+    started: Instant,
+    commands: usize,
+    /// Files read reported so far; an index into the transcript's `files_read`.
+    files_read: usize,
+    tokens: u64,
+    /// Command currently running, for the status line.
+    running: Option<String>,
+    /// Whether a rich status line is on screen and must be cleared before printing.
+    status_shown: bool,
+    last_heartbeat: Instant,
+}
+
+impl Progress {
+    /// Plain mode prints a heartbeat this often when nothing else is printed.
+    const HEARTBEAT: Duration = Duration::from_secs(30);
+    /// Longest command shown, in characters.
+    const MAX_COMMAND_CHARS: usize = 60;
+
+    fn new(mode: ProgressMode) -> Self {
+        let now = Instant::now();
+        Self {
+            mode,
+            started: now,
+            commands: 0,
+            files_read: 0,
+            tokens: 0,
+            running: None,
+            status_shown: false,
+            last_heartbeat: now,
+        }
+    }
+
+    /// Report what `event`, just applied to `monitor`, changed.
+    fn update(&mut self, event: &ServerEvent, monitor: &TurnMonitor) {
+        match event {
+            ServerEvent::ItemStarted { .. } | ServerEvent::ItemCompleted { .. } => {
+                self.catch_up(monitor)
+            }
+            ServerEvent::TokenUsage { .. } => {
+                self.tokens = monitor.usage.usage().total_tokens;
+            }
+            _ => {}
+        }
+    }
+
+    /// Report the commands and files in `monitor` not reported yet.
+    fn catch_up(&mut self, monitor: &TurnMonitor) {
+        if self.mode == ProgressMode::Quiet {
+            return;
+        }
+        let commands: Vec<_> = monitor
+            .transcript
+            .commands()
+            .filter_map(|item| match item {
+                TranscriptItem::CommandExecution {
+                    command, completed, ..
+                } => Some((shorten(command, Self::MAX_COMMAND_CHARS), *completed)),
+                _ => None,
+            })
+            .collect();
+        let files = &monitor.transcript.files_read;
+        // Count first, so the status line redrawn after each line is current.
+        let lines: Vec<_> = commands
+            .iter()
+            .skip(self.commands)
+            .map(|(command, _)| format!("$ {command}"))
+            .chain(files.iter().skip(self.files_read).map(|path| format!("read {path}")))
+            .collect();
+        self.commands = commands.len();
+        self.files_read = files.len();
+        self.tokens = monitor.usage.usage().total_tokens;
+        self.running = commands
+            .into_iter()
+            .rfind(|(_, completed)| !completed)
+            .map(|(command, _)| command);
+        for line in lines {
+            self.println(&format!("{} {line}", self.clock()));
+        }
+    }
+
+    /// Called every second: redraw the status line or print a heartbeat.
+    fn tick(&mut self) {
+        match self.mode {
+            ProgressMode::Rich => self.draw_status(),
+            ProgressMode::Plain if self.last_heartbeat.elapsed() >= Self::HEARTBEAT => {
+                self.println(&format!("{} still working: {}", self.clock(), self.counts()));
+            }
+            _ => {}
+        }
+    }
+
+    /// Print a line of its own, keeping the status line below it.
+    fn println(&mut self, line: &str) {
+        self.clear_status();
+        eprintln!("{line}");
+        self.last_heartbeat = Instant::now();
+        self.draw_status();
+    }
+
+    /// Clear the status line for good and sum up the turn.
+    fn finish(&mut self) {
+        self.clear_status();
+        if self.mode != ProgressMode::Quiet {
+            eprintln!("{} turn finished: {}", self.clock(), self.counts());
+        }
+    }
+
+    fn draw_status(&mut self) {
+        if self.mode != ProgressMode::Rich {
+            return;
+        }
+        let mut status = format!("{} working: {}", self.clock(), self.counts());
+        if let Some(command) = &self.running {
+            status.push_str(&format!(" | $ {command}"));
+        }
+        eprint!("\r\x1b[2K{status}");
+        self.status_shown = true;
+    }
+
+    fn clear_status(&mut self) {
+        if std::mem::take(&mut self.status_shown) {
+            eprint!("\r\x1b[2K");
+        }
+    }
 
-    // 8. Print summary
-    print_summary(&session_name, &cache_dir, &review);
+    /// Elapsed time as `[mm:ss]`, or `[h:mm:ss]` past an hour.
+    fn clock(&self) -> String {
+        let secs = self.started.elapsed().as_secs();
+        match secs / 3600 {
+            0 => format!("[{:02}:{:02}]", secs / 60, secs % 60),
+            hours => format!("[{hours}:{:02}:{:02}]", secs / 60 % 60, secs % 60),
+        }
+    }
 
-    // 9. Shutdown
+    fn counts(&self) -> String {
+        let plural = |n: usize, word: &str| format!("{n} {word}{}", if n == 1 { "" } else { "s" });
+        format!(
+            "{}, {} read, {} tokens",
+            plural(self.commands, "command"),
+            plural(self.files_read, "file"),
+            self.tokens
+        )
+    }
+}
+
+/// `text` on one line, cut to `max_chars` with an ellipsis.
+fn shorten(text: &str, max_chars: usize) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}...", &line[..cut]),
+        None => line,
+    }
+}
+
+/// Sleep until `deadline`, or forever without one.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Gracefully shut down the app server, reporting any unclean teardown step.
+async fn shutdown_client(client: CodexAppServerClient) {
     eprintln!("Shutting down app server...");
     let status = client.shutdown().await;
     if !status.is_clean() {
@@ -226,31 +1033,258 @@ async fn run() -> Result<(), String> {
             eprintln!("  process did not exit within timeout (kill_on_drop will handle)");
         }
     }
+}
+
+/// Listens for SIGINT and (on Unix) SIGTERM once installed.
+struct ShutdownSignals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignals {
+    /// Replace the default signal disposition (terminate) with our handlers.
+    fn install() -> Result<Self, String> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let install = |kind| {
+                signal(kind).map_err(|e| format!("Failed to install signal handler: {e}"))
+            };
+            Ok(Self {
+                interrupt: install(SignalKind::interrupt())?,
+                terminate: install(SignalKind::terminate())?,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {})
+        }
+    }
 
+    /// Wait for the next signal; returns its name.
+    async fn recv(&mut self) -> &'static str {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.interrupt.recv() => "SIGINT",
+                _ = self.terminate.recv() => "SIGTERM",
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+            "SIGINT"
+        }
+    }
+}
+
+/// Stop a running turn early: interrupt it, give the server a moment to flush the
+/// final deltas, save whatever agent text and transcript arrived, then shut the
+/// server down.
+async fn abort_turn(
+    client: CodexAppServerClient,
+    cache_dir: &Path,
+    session_name: &str,
+    thread_id: &str,
+    turn_id: &str,
+    monitor: &mut TurnMonitor,
+) -> String {
+    const GRACE: Duration = Duration::from_secs(5);
+
+    match tokio::time::timeout(GRACE, client.turn_interrupt(thread_id, turn_id)).await {
+        Ok(Ok(())) => {
+            if let Err(e) = client.wait_turn_completed(Some(turn_id), GRACE).await {
+                eprintln!("Warning: turn did not report completion after interrupt: {e}");
+            }
+        }
+        Ok(Err(e)) => eprintln!("Warning: turn/interrupt failed: {e}"),
+        Err(_) => eprintln!("Warning: turn/interrupt timed out"),
+    }
+
+    let partial = client
+        .take_turn_output(thread_id, turn_id)
+        .await
+        .unwrap_or_default();
+    if let Err(e) = save_partial_transcript(cache_dir, session_name, &partial) {
+        eprintln!("Warning: failed to save partial output: {e}");
+    }
+    monitor.drain();
+    if let Err(e) = save_turn_transcript(cache_dir, session_name, &monitor.transcript) {
+        eprintln!("Warning: failed to save turn transcript: {e}");
+    }
+
+    shutdown_client(client).await;
+    partial
+}
+
+/// Save raw agent text from an unfinished turn as `<session>.partial.txt`.
+fn save_partial_transcript(
+    cache_dir: &Path,
+    session_name: &str,
+    text: &str,
+) -> Result<(), String> {
+    std::fs::create_dir_all(cache_dir)
+        .map_err(|e| format!("Failed to create cache dir: {e}"))?;
+    let path = cache_dir.join(format!("{session_name}.partial.txt"));
+    std::fs::write(&path, text).map_err(|e| format!("Write {}: {e}", path.display()))?;
+    eprintln!("Saved partial output ({} bytes): {}", text.len(), path.display());
     Ok(())
 }
 
+/// Save the turn's items (commands, files read, reasoning) as `<session>.transcript.json`.
+fn save_turn_transcript(
+    cache_dir: &Path,
+    session_name: &str,
+    transcript: &TurnTranscript,
+) -> Result<(), String> {
+    std::fs::create_dir_all(cache_dir)
+        .map_err(|e| format!("Failed to create cache dir: {e}"))?;
+    let path = cache_dir.join(format!("{session_name}.transcript.json"));
+    let json =
+        serde_json::to_string_pretty(transcript).map_err(|e| format!("JSON serialize: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Write {}: {e}", path.display()))?;
+    eprintln!(
+        "Saved transcript ({} items, {} commands): {}",
+        transcript.items.len(),
+        transcript.commands().count(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Tokens a review used and what they cost.
+#[derive(Debug, Serialize)]
+struct ReviewUsage {
+    model: String,
+    tokens: TokenUsage,
+    /// Estimated from the price table; `None` when the model has no price.
+    cost_usd: Option<f64>,
+}
+
+impl ReviewUsage {
+    fn new(model: &str, tokens: TokenUsage, prices: &PriceTable) -> Self {
+        Self {
+            model: model.to_string(),
+            cost_usd: prices.cost(model, &tokens),
+            tokens,
+        }
+    }
+}
+
+/// The saved `<session>.json`: the review plus what it cost.
+#[derive(Serialize)]
+struct ReviewReport<'a> {
+    /// Missing when a budget stopped the turn before the review was written.
+    #[serde(flatten)]
+    review: Option<&'a ReviewOutput>,
+    usage: &'a ReviewUsage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    budget_exceeded: Option<&'a BudgetHit>,
+}
+
+/// Built-in prices, overridden by the file named in `CODEX_PRICE_TABLE`.
+fn load_price_table() -> PriceTable {
+    let prices = PriceTable::builtin();
+    match std::env::var_os("CODEX_PRICE_TABLE") {
+        Some(path) => match PriceTable::from_file(&path) {
+            Ok(overrides) => prices.merge(overrides),
+            Err(e) => {
+                eprintln!("Warning: ignoring CODEX_PRICE_TABLE: {e}");
+                prices
+            }
+        },
+        None => prices,
+    }
+}
+
+/// Append this run's usage to the ledger; a failure only warns.
+fn record_usage(ledger: &Path, session_name: &str, outcome: &str, usage: &ReviewUsage) {
+    let record = UsageRecord::new(
+        session_name,
+        &usage.model,
+        outcome,
+        usage.tokens,
+        usage.cost_usd,
+    );
+    if let Err(e) = append_usage_record(ledger, &record) {
+        eprintln!("Warning: failed to record usage: {e}");
+    }
+}
+
 fn save_review_json(
     cache_dir: &Path,
     session_name: &str,
-    review: &ReviewOutput,
+    review: Option<&ReviewOutput>,
+    usage: &ReviewUsage,
+    budget_exceeded: Option<&BudgetHit>,
 ) -> Result<(), String> {
     let path = cache_dir.join(format!("{session_name}.json"));
-    let json = serde_json::to_string_pretty(review).map_err(|e| format!("JSON serialize: {e}"))?;
+    let report = ReviewReport {
+        review,
+        usage,
+        budget_exceeded,
+    };
+    let json = serde_json::to_string_pretty(&report).map_err(|e| format!("JSON serialize: {e}"))?;
     std::fs::write(&path, json).map_err(|e| format!("Write {}: {e}", path.display()))?;
     eprintln!("Saved: {}", path.display());
     Ok(())
 }
 
-fn save_review_markdown(
+/// Write the shareable `<session>.html`, with snippets from the reviewed tree.
+fn save_review_html(
+    cache_dir: &Path,
+    session_name: &str,
+    review: &ReviewOutput,
+    project_path: &Path,
+) -> Result<(), String> {
+    let path = cache_dir.join(format!("{session_name}.html"));
+    let html = report::html::render(review, session_name, Some(project_path));
+    std::fs::write(&path, html).map_err(|e| format!("Write {}: {e}", path.display()))?;
+    eprintln!("Saved: {}", path.display());
+    Ok(())
+}
+
+/// Write `<session>.<ext>` for each requested report format.
+fn save_review_reports(
     cache_dir: &Path,
     session_name: &str,
     review: &ReviewOutput,
+    formats: &[ReportFormat],
+    options: &ReportOptions,
+) -> Result<(), String> {
+    for format in formats {
+        let path = cache_dir.join(format!("{session_name}.{}", format.extension()));
+        std::fs::write(&path, format.render(review, options))
+            .map_err(|e| format!("Write {}: {e}", path.display()))?;
+        eprintln!("Saved: {}", path.display());
+    }
+    Ok(())
+}
+
+fn save_review_markdown(
+    cache_dir: &Path,
+    session_name: &str,
+    review: Option<&ReviewOutput>,
+    budget_exceeded: Option<&BudgetHit>,
 ) -> Result<(), String> {
     let path = cache_dir.join(format!("{session_name}.md"));
     let mut md = String::new();
 
     md.push_str(&format!("# Code Review: {session_name}\n\n"));
+    if let Some(hit) = budget_exceeded {
+        md.push_str(&format!("> **Stopped early**: {hit}.\n\n"));
+    }
+    let Some(review) = review else {
+        md.push_str(&format!(
+            "No review was produced before the turn was stopped. \
+             The agent's partial output is in `{session_name}.partial.txt`.\n"
+        ));
+        std::fs::write(&path, md).map_err(|e| format!("Write {}: {e}", path.display()))?;
+        eprintln!("Saved: {}", path.display());
+        return Ok(());
+    };
     md.push_str(&format!("**Score**: {}/10\n\n", review.score));
     md.push_str(&format!("## Summary\n\n{}\n\n", review.summary));
 
@@ -286,7 +1320,12 @@ fn save_review_markdown(
     Ok(())
 }
 
-fn print_summary(session_name: &str, cache_dir: &Path, review: &ReviewOutput) {
+fn print_summary(
+    session_name: &str,
+    cache_dir: &Path,
+    review: &ReviewOutput,
+    usage: &ReviewUsage,
+) {
     let mut counts = [0u32; 4]; // CRITICAL, HIGH, MEDIUM, LOW
     for f in &review.findings {
         match f.severity {
@@ -306,6 +1345,10 @@ fn print_summary(session_name: &str, cache_dir: &Path, review: &ReviewOutput) {
         "**Full report**: {}",
         cache_dir.join(format!("{session_name}.md")).display()
     );
+    println!(
+        "**HTML report**: {}",
+        cache_dir.join(format!("{session_name}.html")).display()
+    );
     println!();
     println!("| Severity | Count |");
     println!("|----------|-------|");
@@ -315,6 +1358,22 @@ fn print_summary(session_name: &str, cache_dir: &Path, review: &ReviewOutput) {
     println!("| Low      | {} |", counts[3]);
     println!();
     println!("**Summary**: {}", review.summary);
+    println!();
+    let tokens = &usage.tokens;
+    println!(
+        "**Tokens**: {} input ({} cached), {} output ({} reasoning)",
+        tokens.input_tokens,
+        tokens.cached_input_tokens,
+        tokens.output_tokens,
+        tokens.reasoning_output_tokens
+    );
+    match usage.cost_usd {
+        Some(cost) => println!("**Estimated cost**: ${cost:.4} ({})", usage.model),
+        None => println!(
+            "**Estimated cost**: unknown (no price for {}; see CODEX_PRICE_TABLE)",
+            usage.model
+        ),
+    }
 }
 
 /// Read turn timeout from `CODEX_TURN_TIMEOUT` env var (seconds).
@@ -334,6 +1393,31 @@ fn parse_turn_timeout() -> Duration {
     }
 }
 
+/// Read a number of seconds from the env var `name`; 0 disables the setting.
+fn parse_secs_env(name: &str, default_secs: u64) -> Option<Duration> {
+    let secs = match std::env::var(name) {
+        Ok(val) => val.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("Warning: invalid {name}={val:?}, using default {default_secs}s");
+            default_secs
+        }),
+        Err(_) => default_secs,
+    };
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Read how often a crashed app server is restarted and the turn retried from
+/// `CODEX_TURN_RETRIES`. Default: 1.
+fn parse_turn_retries() -> u32 {
+    const DEFAULT_RETRIES: u32 = 1;
+    match std::env::var("CODEX_TURN_RETRIES") {
+        Ok(val) => val.parse::<u32>().unwrap_or_else(|_| {
+            eprintln!("Warning: invalid CODEX_TURN_RETRIES={val:?}, using default {DEFAULT_RETRIES}");
+            DEFAULT_RETRIES
+        }),
+        Err(_) => DEFAULT_RETRIES,
+    }
+}
+
 /// Parse the last valid ReviewOutput from concatenated JSON objects.
 /// Codex with outputSchema streams multiple JSON objects: reasoning steps
 /// followed by the final structured answer. We extract each top-level JSON
@@ -457,4 +1541,64 @@ mod tests {
         let review = parse_last_review_output(text).unwrap();
         assert!(review.summary.contains("hello"));
     }
+
+    #[test]
+    fn budget_values_must_be_positive() {
+        assert_eq!(parse_budget_value::<u64>("--max-tokens", "5000"), Ok(5000));
+        assert_eq!(parse_budget_value::<f64>("--max-cost", "0.25"), Ok(0.25));
+        assert!(parse_budget_value::<u64>("--max-tokens", "0").is_err());
+        assert!(parse_budget_value::<f64>("--max-cost", "-1").is_err());
+        assert!(parse_budget_value::<f64>("--max-cost", "NaN").is_err());
+    }
+
+    #[test]
+    fn budget_reports_first_limit_exceeded() {
+        let prices = PriceTable::new().price("m", ModelPrice::new(1.0, 0.1, 10.0));
+        let usage = |input_tokens, output_tokens| TokenUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: input_tokens + output_tokens,
+            ..TokenUsage::default()
+        };
+
+        let tokens = Budget::new(Some(1_000), None, "m", &prices).unwrap();
+        assert!(tokens.check(&usage(600, 400)).is_none());
+        assert!(matches!(
+            tokens.check(&usage(600, 401)),
+            Some(BudgetHit::Tokens { used_tokens: 1_001, .. })
+        ));
+
+        // 1M input tokens at $1/M plus 20k output tokens at $10/M is $1.20.
+        let cost = Budget::new(None, Some(1.0), "m", &prices).unwrap();
+        assert!(cost.check(&usage(900_000, 0)).is_none());
+        assert!(matches!(
+            cost.check(&usage(1_000_000, 20_000)),
+            Some(BudgetHit::Cost { .. })
+        ));
+
+        assert!(Budget::new(None, Some(1.0), "unpriced", &prices).is_err());
+    }
+
+    #[test]
+    fn shorten_keeps_commands_on_one_line() {
+        assert_eq!(shorten("cargo  test\n  --all", 60), "cargo test --all");
+        assert_eq!(shorten("rg -n needle src/", 5), "rg -n...");
+        assert_eq!(shorten("héllo wörld", 4), "héll...");
+    }
+
+    #[test]
+    fn progress_mode_parses_known_modes() {
+        assert_eq!(ProgressMode::parse("rich"), Ok(ProgressMode::Rich));
+        assert_eq!(ProgressMode::parse("plain"), Ok(ProgressMode::Plain));
+        assert_eq!(ProgressMode::parse("quiet"), Ok(ProgressMode::Quiet));
+        assert!(ProgressMode::parse("verbose").is_err());
+    }
+
+    #[test]
+    fn formats_parse_as_a_comma_separated_list() {
+        assert_eq!(parse_formats("sarif"), Ok(vec![ReportFormat::Sarif]));
+        assert_eq!(parse_formats(" sarif ,"), Ok(vec![ReportFormat::Sarif]));
+        let err = parse_formats("sarif,pdf").unwrap_err();
+        assert!(err.contains("\"pdf\"") && err.contains("sarif"), "{err}");
+    }
 }
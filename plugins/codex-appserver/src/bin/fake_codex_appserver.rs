@@ -0,0 +1,268 @@
+//! Test binary: a scriptable stand-in for `codex app-server`.
+//!
+//! Speaks the subset of the protocol the review client uses, over stdio, driven by
+//! a JSON scenario file. Point the client at it with `CODEX_BIN`; the `app-server`
+//! subcommand and any `-c key=value` overrides are accepted and ignored.
+//!
+//! Usage:
+//!   FAKE_CODEX_SCENARIO=<scenario.json> fake-codex-appserver [-c k=v]... app-server
+//!
+//! Scenario format:
+//!
+//! ```json
+//! {
+//!   "stderr": ["line written to stderr at startup"],
+//!   "methods": {
+//!     "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+//!     "turn/start": {
+//!       "result": { "turn": { "id": "turn_1", "status": "inProgress" } },
+//!       "messages": [
+//!         { "method": "item/agentMessage/delta",
+//!           "params": { "threadId": "thr_1", "turnId": "turn_1", "delta": "..." } },
+//!         { "method": "turn/completed",
+//!           "params": { "threadId": "thr_1", "turn": { "id": "turn_1", "status": "completed" } } }
+//!       ]
+//!     },
+//!     "model/list": { "error": { "code": -32001, "message": "Server overloaded" } }
+//!   }
+//! }
+//! ```
+//!
+//! Each method maps to a step, or to a list of steps used for successive calls (the
+//! last one repeats). A step may set `result` or `error`, `messages` to send after
+//! the response, `delay_ms` before answering, `messages_delay_ms` between the
+//! response and the messages, `stderr` lines to print, `exit` to terminate with
+//! that code instead of answering, `exit_after` to terminate with that code once
+//! the response and messages are out, and `hang` to never answer.
+//!
+//! A scenario may instead hold `"runs": [scenario, ...]`: each process start uses
+//! the next entry (the last one repeats), counted in `<scenario>.runs` next to the
+//! scenario file. This scripts a server that crashes once and then recovers.
+//! Each process also writes its pid to `<scenario>.pid`, so tests can check that
+//! the client did not leave it running.
+//!
+//! Without a scenario entry, `initialize`, `thread/resume` and `shutdown` get
+//! plausible defaults, the `exit` notification ends the process, and any other
+//! request is answered with "method not found". The default `initialize` reports
+//! the oldest codex version with every feature the client uses.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use codex_appserver::appserver::protocol::{JsonRpcError, JsonRpcReply};
+use codex_appserver::appserver::OUTPUT_SCHEMA_MIN_VERSION;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Environment variable naming the scenario file.
+const SCENARIO_ENV: &str = "FAKE_CODEX_SCENARIO";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Scenario {
+    stderr: Vec<String>,
+    methods: HashMap<String, Steps>,
+    runs: Vec<Scenario>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Steps {
+    One(Step),
+    Many(Vec<Step>),
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Step {
+    result: Option<Value>,
+    error: Option<JsonRpcError>,
+    messages: Vec<Value>,
+    delay_ms: u64,
+    messages_delay_ms: u64,
+    stderr: Vec<String>,
+    exit: Option<i32>,
+    exit_after: Option<i32>,
+    hang: bool,
+}
+
+impl Steps {
+    fn get(&self, call: usize) -> Option<&Step> {
+        match self {
+            Steps::One(step) => Some(step),
+            Steps::Many(steps) => steps.get(call).or(steps.last()),
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("fake-codex-appserver: {e}");
+        std::process::exit(2);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.iter().any(|a| a == "app-server") {
+        return Err("expected the `app-server` subcommand".to_string());
+    }
+    let scenario = match std::env::var_os(SCENARIO_ENV) {
+        Some(path) => {
+            write_pid_file(Path::new(&path))?;
+            load_scenario(Path::new(&path))?
+        }
+        None => Scenario::default(),
+    };
+
+    for line in &scenario.stderr {
+        eprintln!("{line}");
+    }
+
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout().lock();
+    let mut calls: HashMap<String, usize> = HashMap::new();
+
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| format!("read stdin: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let msg: Value =
+            serde_json::from_str(&line).map_err(|e| format!("invalid JSON from client: {e}"))?;
+        let Some(method) = msg.get("method").and_then(|m| m.as_str()) else {
+            continue; // reply to a server request
+        };
+        let id = msg.get("id").and_then(|v| v.as_u64());
+        let call = calls.entry(method.to_string()).or_default();
+        let step = scenario.methods.get(method).and_then(|s| s.get(*call)).cloned();
+        *call += 1;
+
+        let Some(id) = id else {
+            // Notification: only `exit` (or a scripted step) has an effect.
+            if let Some(step) = step {
+                play_step(&mut stdout, None, method, &msg, step)?;
+            } else if method == "exit" {
+                return Ok(());
+            }
+            continue;
+        };
+        let step = step.unwrap_or_else(|| default_step(method, &msg));
+        play_step(&mut stdout, Some(id), method, &msg, step)?;
+    }
+    Ok(())
+}
+
+/// Answer one client message as scripted by `step`.
+fn play_step(
+    out: &mut impl Write,
+    id: Option<u64>,
+    method: &str,
+    msg: &Value,
+    step: Step,
+) -> Result<(), String> {
+    for line in &step.stderr {
+        eprintln!("{line}");
+    }
+    if step.delay_ms > 0 {
+        std::thread::sleep(Duration::from_millis(step.delay_ms));
+    }
+    if let Some(code) = step.exit {
+        std::process::exit(code);
+    }
+    if step.hang {
+        return Ok(());
+    }
+    if let Some(id) = id {
+        let outcome = match step.error {
+            Some(error) => Err(error),
+            None => Ok(step.result.unwrap_or(Value::Null)),
+        };
+        write_message(out, &serde_json::to_value(JsonRpcReply::new(id, outcome)).unwrap())?;
+    }
+    if step.messages_delay_ms > 0 {
+        std::thread::sleep(Duration::from_millis(step.messages_delay_ms));
+    }
+    for message in &step.messages {
+        write_message(out, message)?;
+    }
+    if let Some(code) = step.exit_after {
+        std::process::exit(code);
+    }
+    if method == "exit" && msg.get("id").is_none() {
+        std::process::exit(0);
+    }
+    Ok(())
+}
+
+/// Behaviour for methods the scenario does not mention.
+fn default_step(method: &str, msg: &Value) -> Step {
+    let result = match method {
+        "initialize" => json!({
+            "userAgent": concat!("fake-codex-appserver/", env!("CARGO_PKG_VERSION")),
+            "serverInfo": { "name": "fake-codex-appserver", "version": OUTPUT_SCHEMA_MIN_VERSION.to_string() }
+        }),
+        "thread/resume" => json!({ "thread": { "id": msg["params"]["threadId"] } }),
+        "shutdown" => Value::Null,
+        _ => {
+            return Step {
+                error: Some(JsonRpcError::method_not_found(method)),
+                ..Step::default()
+            }
+        }
+    };
+    Step {
+        result: Some(result),
+        ..Step::default()
+    }
+}
+
+/// Write one JSONL message, adding `"jsonrpc": "2.0"` if the scenario left it out.
+fn write_message(out: &mut impl Write, message: &Value) -> Result<(), String> {
+    let mut message = message.clone();
+    if let Value::Object(map) = &mut message {
+        map.entry("jsonrpc").or_insert_with(|| json!("2.0"));
+    }
+    writeln!(out, "{message}")
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("write stdout: {e}"))
+}
+
+/// Load the scenario, resolving `runs` to the entry for this process start.
+fn load_scenario(path: &Path) -> Result<Scenario, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("read scenario {}: {e}", path.display()))?;
+    let mut scenario: Scenario = serde_json::from_str(&text)
+        .map_err(|e| format!("invalid scenario {}: {e}", path.display()))?;
+    if scenario.runs.is_empty() {
+        return Ok(scenario);
+    }
+    let run = next_run_index(path)?;
+    let last = scenario.runs.len() - 1;
+    Ok(scenario.runs.swap_remove(run.min(last)))
+}
+
+/// Record this process's pid next to the scenario file.
+fn write_pid_file(scenario: &Path) -> Result<(), String> {
+    let mut pid_file = scenario.as_os_str().to_owned();
+    pid_file.push(".pid");
+    let pid_file = PathBuf::from(pid_file);
+    std::fs::write(&pid_file, std::process::id().to_string())
+        .map_err(|e| format!("write {}: {e}", pid_file.display()))
+}
+
+/// Read and bump the per-scenario start counter.
+fn next_run_index(scenario: &Path) -> Result<usize, String> {
+    let mut counter = scenario.as_os_str().to_owned();
+    counter.push(".runs");
+    let counter = PathBuf::from(counter);
+    let run = std::fs::read_to_string(&counter)
+        .ok()
+        .and_then(|s| s.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    std::fs::write(&counter, (run + 1).to_string())
+        .map_err(|e| format!("write {}: {e}", counter.display()))?;
+    Ok(run)
+}
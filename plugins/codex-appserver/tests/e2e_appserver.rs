@@ -2,12 +2,23 @@
 //! Tests protocol types, serialization, deserialization, and review output parsing.
 //! Does NOT require a running codex process — tests the client library logic only.
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use codex_appserver::appserver::client::ShutdownStatus;
 use codex_appserver::appserver::protocol::{
-    review_output_schema, Dimension, Finding, JsonRpcError, JsonRpcNotification, JsonRpcRequest,
-    ReviewOutput, ServerMessage, Severity,
+    review_output_schema, ApprovalPolicy, Dimension, Finding, InitializeParams, JsonRpcError,
+    JsonRpcNotification, JsonRpcReply, JsonRpcRequest, ModelListParams, RequestId,
+    ReviewOutput, SandboxMode, ServerMessage, Severity, ThreadStartParams, TurnCompletedParams,
+    TurnStartParams, TurnStatus,
+};
+use codex_appserver::appserver::{
+    read_transcript, AppServerError, ClientConfig, CodexAppServerClient, Direction,
+    DuplexTransport, ReplayTransport, RetryPolicy, ServerEvent, SupervisedClient,
+    TranscriptRecord,
 };
 use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
 // ============================================================================
 // JsonRpcRequest — serialization
@@ -88,7 +99,7 @@ fn parse_response_with_result() {
 
     match msg {
         ServerMessage::Response(resp) => {
-            assert_eq!(resp.id, Some(1));
+            assert_eq!(resp.id, Some(RequestId::Number(1)));
             assert!(resp.error.is_none());
             let result = resp.result.unwrap();
             assert_eq!(result["id"], "thr_123");
@@ -104,7 +115,7 @@ fn parse_response_with_error() {
 
     match msg {
         ServerMessage::Response(resp) => {
-            assert_eq!(resp.id, Some(5));
+            assert_eq!(resp.id, Some(RequestId::Number(5)));
             let err = resp.error.unwrap();
             assert_eq!(err.code, -32600);
             assert_eq!(err.message, "Invalid request");
@@ -120,7 +131,7 @@ fn parse_response_with_null_result() {
 
     match msg {
         ServerMessage::Response(resp) => {
-            assert_eq!(resp.id, Some(10));
+            assert_eq!(resp.id, Some(RequestId::Number(10)));
             assert!(resp.error.is_none());
             // serde deserializes JSON null as None for Option<Value>
             assert!(resp.result.is_none());
@@ -191,6 +202,54 @@ fn parse_turn_completed_with_failure() {
     }
 }
 
+// ============================================================================
+// ServerMessage::parse — server-initiated requests
+// ============================================================================
+
+#[test]
+fn parse_server_request_with_id_and_method() {
+    let line = r#"{"jsonrpc":"2.0","id":5,"method":"item/commandExecution/requestApproval","params":{"threadId":"thr_1","command":"rm -rf /"}}"#;
+    let msg = ServerMessage::parse(line).unwrap();
+    match msg {
+        ServerMessage::Request { id, method, params } => {
+            assert_eq!(id, RequestId::Number(5));
+            assert_eq!(method, "item/commandExecution/requestApproval");
+            assert_eq!(params["command"], "rm -rf /");
+        }
+        _ => panic!("Expected Request"),
+    }
+}
+
+#[test]
+fn server_request_string_id_is_echoed_back_unchanged() {
+    let line = r#"{"jsonrpc":"2.0","id":"req-7","method":"item/fileChange/requestApproval","params":{}}"#;
+    let ServerMessage::Request { id, .. } = ServerMessage::parse(line).unwrap() else {
+        panic!("Expected Request");
+    };
+    assert_eq!(id, RequestId::from("req-7"));
+    let reply = serde_json::to_value(JsonRpcReply::new(id, Ok(json!({})))).unwrap();
+    assert_eq!(reply["id"], "req-7");
+}
+
+#[test]
+fn json_rpc_reply_serializes_result_without_error() {
+    let reply = JsonRpcReply::new(5, Ok(json!({"decision": "decline"})));
+    let parsed: Value = serde_json::to_value(&reply).unwrap();
+    assert_eq!(parsed["jsonrpc"], "2.0");
+    assert_eq!(parsed["id"], 5);
+    assert_eq!(parsed["result"]["decision"], "decline");
+    assert!(parsed.get("error").is_none());
+}
+
+#[test]
+fn json_rpc_reply_serializes_error_without_result() {
+    let reply = JsonRpcReply::new(6, Err(JsonRpcError::method_not_found("tool/call")));
+    let parsed: Value = serde_json::to_value(&reply).unwrap();
+    assert_eq!(parsed["error"]["code"], -32601);
+    assert!(parsed.get("result").is_none());
+    assert!(parsed["error"].get("data").is_none());
+}
+
 // ============================================================================
 // ServerMessage::parse — error cases
 // ============================================================================
@@ -199,14 +258,14 @@ fn parse_turn_completed_with_failure() {
 fn parse_invalid_json_returns_error() {
     let result = ServerMessage::parse("not json at all");
     assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Invalid JSON"));
+    assert!(result.unwrap_err().to_string().contains("Invalid JSON"));
 }
 
 #[test]
 fn parse_empty_object_returns_error() {
     let result = ServerMessage::parse(r#"{"jsonrpc":"2.0"}"#);
     assert!(result.is_err());
-    assert!(result.unwrap_err().contains("neither id nor method"));
+    assert!(result.unwrap_err().to_string().contains("neither id nor method"));
 }
 
 #[test]
@@ -511,6 +570,7 @@ fn simulate_full_review_stream() {
                 }
                 _ => {}
             },
+            ServerMessage::Request { method, .. } => panic!("Unexpected server request: {method}"),
         }
     }
 
@@ -534,7 +594,7 @@ fn parse_response_with_id_zero() {
     let line = r#"{"jsonrpc":"2.0","id":0,"result":{}}"#;
     let msg = ServerMessage::parse(line).unwrap();
     match msg {
-        ServerMessage::Response(resp) => assert_eq!(resp.id, Some(0)),
+        ServerMessage::Response(resp) => assert_eq!(resp.id, Some(RequestId::Number(0))),
         _ => panic!("Expected Response"),
     }
 }
@@ -1079,7 +1139,7 @@ fn agent_text_accumulation_large_delta() {
 #[test]
 fn agent_text_accumulation_unicode_delta() {
     let line = r#"{"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"delta":"한글 테스트 🎉"}}"#;
-    let msg = ServerMessage::parse(&line).unwrap();
+    let msg = ServerMessage::parse(line).unwrap();
     match msg {
         ServerMessage::Notification { params, .. } => {
             let delta = params["delta"].as_str().unwrap();
@@ -1128,7 +1188,7 @@ fn parse_response_with_both_result_and_error() {
     let msg = ServerMessage::parse(line).unwrap();
     match msg {
         ServerMessage::Response(resp) => {
-            assert_eq!(resp.id, Some(7));
+            assert_eq!(resp.id, Some(RequestId::Number(7)));
             // Both should be preserved — caller decides which to use
             assert!(resp.result.is_some());
             assert!(resp.error.is_some());
@@ -1201,6 +1261,7 @@ fn simulate_review_with_turn_correlation() {
                 }
                 _ => {}
             },
+            ServerMessage::Request { method, .. } => panic!("Unexpected server request: {method}"),
         }
     }
 
@@ -1216,3 +1277,1028 @@ fn simulate_review_with_turn_correlation() {
     let comp = matching_completion.unwrap();
     assert_eq!(comp["turn"]["status"], "completed");
 }
+
+// ============================================================================
+// Client over an in-memory duplex transport
+// ============================================================================
+
+/// Spawn a stand-in server on the far end of a duplex pipe. `respond` maps each
+/// incoming request/notification to the lines to write back (possibly none).
+fn spawn_stand_in_server<F>(respond: F) -> CodexAppServerClient
+where
+    F: FnMut(&Value) -> Vec<Value> + Send + 'static,
+{
+    spawn_stand_in_server_with_config(&ClientConfig::new(), respond)
+}
+
+/// [`spawn_stand_in_server`] with a client built from `config`.
+fn spawn_stand_in_server_with_config<F>(
+    config: &ClientConfig,
+    mut respond: F,
+) -> CodexAppServerClient
+where
+    F: FnMut(&Value) -> Vec<Value> + Send + 'static,
+{
+    let (transport, server) = DuplexTransport::pair(64 * 1024);
+    tokio::spawn(async move {
+        let (read, mut write) = tokio::io::split(server);
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let msg: Value = serde_json::from_str(&line).unwrap();
+            for out in respond(&msg) {
+                let mut s = out.to_string();
+                s.push('\n');
+                if write.write_all(s.as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    CodexAppServerClient::connect_with_config(transport, config)
+}
+
+#[tokio::test]
+async fn duplex_request_roundtrip() {
+    let client = spawn_stand_in_server(|msg| match msg["method"].as_str() {
+        Some("initialize") => vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":{"userAgent":"stand-in"}})],
+        _ => vec![],
+    });
+
+    let result = client.request("initialize", json!({})).await.unwrap();
+    assert_eq!(result["userAgent"], "stand-in");
+}
+
+#[tokio::test]
+async fn duplex_turn_stream_accumulates_text_and_completes() {
+    let client = spawn_stand_in_server(|msg| match msg["method"].as_str() {
+        Some("turn/start") => vec![
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{"turn":{"id":"turn_1"}}}),
+            json!({"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"threadId":"thr_1","turnId":"turn_1","delta":"hello "}}),
+            json!({"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"threadId":"thr_1","turnId":"turn_1","delta":"world"}}),
+            json!({"jsonrpc":"2.0","method":"turn/completed","params":{"turn":{"id":"turn_1","status":"completed"}}}),
+        ],
+        _ => vec![],
+    });
+
+    client.request("turn/start", json!({})).await.unwrap();
+    let completed = client
+        .wait_turn_completed(Some("turn_1"), std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(completed["turn"]["status"], "completed");
+    assert_eq!(
+        client.turn_output("thr_1", "turn_1").await.as_deref(),
+        Some("hello world")
+    );
+}
+
+#[tokio::test]
+async fn duplex_server_eof_fails_pending_request() {
+    let (transport, server) = DuplexTransport::pair(1024);
+    let client = CodexAppServerClient::connect(transport);
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(server).lines();
+        // Read the request, then hang up without answering.
+        let _ = lines.next_line().await;
+    });
+
+    let err = client.request("thread/start", json!({})).await.unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+    assert_eq!(err.to_string(), "App server exited");
+}
+
+#[tokio::test]
+async fn duplex_shutdown_without_child_is_clean() {
+    let client = spawn_stand_in_server(|msg| match (msg["method"].as_str(), msg.get("id")) {
+        (Some("shutdown"), Some(id)) => vec![json!({"jsonrpc":"2.0","id":id,"result":null})],
+        _ => vec![],
+    });
+
+    let status = client.shutdown().await;
+    assert!(status.is_clean(), "{status:?}");
+}
+
+#[tokio::test]
+async fn duplex_approval_request_is_declined_by_default() {
+    let (reply_tx, mut reply_rx) = tokio::sync::mpsc::unbounded_channel();
+    let client = spawn_stand_in_server(move |msg| {
+        if msg.get("method").is_none() {
+            // Client's reply to our approval request.
+            let _ = reply_tx.send(msg.clone());
+            return vec![];
+        }
+        match msg["method"].as_str() {
+            Some("turn/start") => vec![
+                json!({"jsonrpc":"2.0","id":900,"method":"item/commandExecution/requestApproval","params":{"command":"touch x"}}),
+                json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+            ],
+            _ => vec![],
+        }
+    });
+
+    client.request("turn/start", json!({})).await.unwrap();
+    let reply = tokio::time::timeout(std::time::Duration::from_secs(5), reply_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply["id"], 900);
+    assert_eq!(reply["result"]["decision"], "decline");
+}
+
+#[tokio::test]
+async fn duplex_approval_request_with_string_id_is_answered() {
+    let (reply_tx, mut reply_rx) = tokio::sync::mpsc::unbounded_channel();
+    let client = spawn_stand_in_server(move |msg| {
+        if msg.get("method").is_none() {
+            let _ = reply_tx.send(msg.clone());
+            return vec![];
+        }
+        vec![
+            json!({"jsonrpc":"2.0","id":"approval-1","method":"item/fileChange/requestApproval","params":{}}),
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+        ]
+    });
+
+    client.request("turn/start", json!({})).await.unwrap();
+    let reply = tokio::time::timeout(std::time::Duration::from_secs(5), reply_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply["id"], "approval-1");
+    assert_eq!(reply["result"]["decision"], "decline");
+}
+
+#[tokio::test]
+async fn duplex_custom_request_handler_and_unknown_method() {
+    let (reply_tx, mut reply_rx) = tokio::sync::mpsc::unbounded_channel();
+    let client = spawn_stand_in_server(move |msg| {
+        if msg.get("method").is_none() {
+            let _ = reply_tx.send(msg.clone());
+            return vec![];
+        }
+        vec![
+            json!({"jsonrpc":"2.0","id":901,"method":"custom/ask","params":{"q":1}}),
+            json!({"jsonrpc":"2.0","id":902,"method":"unknown/ask","params":{}}),
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+        ]
+    });
+    client
+        .set_request_handler("custom/ask", std::sync::Arc::new(|p| Ok(json!({"echo": p["q"]}))))
+        .await;
+
+    client.request("ping", Value::Null).await.unwrap();
+    let first = reply_rx.recv().await.unwrap();
+    let second = reply_rx.recv().await.unwrap();
+    assert_eq!(first["id"], 901);
+    assert_eq!(first["result"]["echo"], 1);
+    assert_eq!(second["id"], 902);
+    assert_eq!(second["error"]["code"], -32601);
+}
+
+// ============================================================================
+// Event subscription
+// ============================================================================
+
+#[tokio::test]
+async fn subscribe_receives_typed_events_in_order() {
+    let client = spawn_stand_in_server(|msg| match msg["method"].as_str() {
+        Some("turn/start") => vec![
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+            json!({"jsonrpc":"2.0","method":"item/started","params":{"threadId":"thr_1","turnId":"turn_1","item":{"id":"cmd_1","type":"commandExecution","command":"ls"}}}),
+            json!({"jsonrpc":"2.0","method":"item/commandExecution/outputDelta","params":{"threadId":"thr_1","turnId":"turn_1","itemId":"cmd_1","delta":"src\n"}}),
+            json!({"jsonrpc":"2.0","method":"item/completed","params":{"threadId":"thr_1","turnId":"turn_1","item":{"id":"cmd_1","type":"commandExecution","exitCode":0}}}),
+            json!({"jsonrpc":"2.0","method":"turn/completed","params":{"threadId":"thr_1","turn":{"id":"turn_1","status":"completed"}}}),
+        ],
+        _ => vec![],
+    });
+    let mut events = client.subscribe();
+
+    client.request("turn/start", json!({})).await.unwrap();
+
+    match events.next().await.unwrap() {
+        ServerEvent::ItemStarted { item, turn_id, .. } => {
+            assert_eq!(item["command"], "ls");
+            assert_eq!(turn_id.as_deref(), Some("turn_1"));
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    match events.next().await.unwrap() {
+        ServerEvent::CommandOutputDelta { item_id, delta, .. } => {
+            assert_eq!(item_id.as_deref(), Some("cmd_1"));
+            assert_eq!(delta, "src\n");
+        }
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(matches!(
+        events.next().await.unwrap(),
+        ServerEvent::ItemCompleted { .. }
+    ));
+    match events.next().await.unwrap() {
+        ServerEvent::TurnCompleted { turn, .. } => assert_eq!(turn["status"], "completed"),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn subscription_ends_when_server_exits() {
+    let (transport, server) = DuplexTransport::pair(1024);
+    let client = CodexAppServerClient::connect(transport);
+    let mut events = client.subscribe();
+    drop(server);
+
+    let next = tokio::time::timeout(std::time::Duration::from_secs(5), events.next())
+        .await
+        .unwrap();
+    assert!(next.is_none());
+    // Subscribing after the reader is gone yields an already-ended stream.
+    assert!(client.subscribe().next().await.is_none());
+}
+
+// ============================================================================
+// AppServerError — structured client errors
+// ============================================================================
+
+#[tokio::test]
+async fn rpc_error_keeps_code_and_data() {
+    let client = spawn_stand_in_server(|msg| {
+        vec![json!({"jsonrpc":"2.0","id":msg["id"],"error":{"code":-32001,"message":"Server overloaded","data":{"retryAfterMs":50}}})]
+    });
+
+    let err = client.request("model/list", json!({})).await.unwrap_err();
+    assert_eq!(err.rpc_code(), Some(-32001));
+    assert!(err.is_backpressure());
+    match &err {
+        AppServerError::Rpc { method, error } => {
+            assert_eq!(method, "model/list");
+            assert_eq!(error.data.as_ref().unwrap()["retryAfterMs"], 50);
+        }
+        other => panic!("Expected Rpc, got {other:?}"),
+    }
+    assert_eq!(
+        err.to_string(),
+        "Request 'model/list' failed: JSON-RPC error -32001: Server overloaded"
+    );
+}
+
+#[tokio::test]
+async fn request_timeout_is_distinct_from_rpc_error() {
+    let client = spawn_stand_in_server(|_| vec![]);
+
+    let err = client
+        .request_with_timeout("thread/start", json!({}), std::time::Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(err.rpc_code(), None);
+    match err {
+        AppServerError::Timeout {
+            operation, after, ..
+        } => {
+            assert!(operation.contains("thread/start"));
+            assert_eq!(after, std::time::Duration::from_millis(50));
+        }
+        other => panic!("Expected Timeout, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn wait_turn_completed_reports_timeout_and_server_exit() {
+    let client = spawn_stand_in_server(|_| vec![]);
+    let err = client
+        .wait_turn_completed(Some("turn_1"), std::time::Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(err.is_timeout());
+
+    let (transport, server) = DuplexTransport::pair(1024);
+    let client = CodexAppServerClient::connect(transport);
+    drop(server);
+    let err = client
+        .wait_turn_completed(None, std::time::Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+}
+
+#[test]
+fn parse_errors_are_typed() {
+    assert!(matches!(
+        ServerMessage::parse("not json"),
+        Err(AppServerError::Json { .. })
+    ));
+    assert!(matches!(
+        ServerMessage::parse("{}"),
+        Err(AppServerError::Protocol(_))
+    ));
+}
+
+// ============================================================================
+// Concurrent use of a shared client handle
+// ============================================================================
+
+#[tokio::test]
+async fn request_while_another_task_waits_for_turn() {
+    // The server completes the turn only after it sees a second, unrelated request.
+    let client = spawn_stand_in_server(|msg| match msg["method"].as_str() {
+        Some("turn/start") => vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":{"turn":{"id":"turn_a"}}})],
+        Some("model/list") => vec![
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{"data":[]}}),
+            json!({"jsonrpc":"2.0","method":"turn/completed","params":{"turn":{"id":"turn_a","status":"completed"}}}),
+        ],
+        _ => vec![],
+    });
+
+    client.request("turn/start", json!({})).await.unwrap();
+    let waiter = {
+        let client = client.clone();
+        tokio::spawn(async move {
+            client
+                .wait_turn_completed(Some("turn_a"), std::time::Duration::from_secs(5))
+                .await
+        })
+    };
+
+    let models = client.request("model/list", json!({})).await.unwrap();
+    assert!(models["data"].is_array());
+    let completed = waiter.await.unwrap().unwrap();
+    assert_eq!(completed["turn"]["id"], "turn_a");
+}
+
+#[tokio::test]
+async fn concurrent_waiters_each_get_their_own_turn() {
+    let client = spawn_stand_in_server(|msg| match msg["method"].as_str() {
+        Some("go") => vec![
+            json!({"jsonrpc":"2.0","method":"turn/completed","params":{"turn":{"id":"turn_2","status":"completed"}}}),
+            json!({"jsonrpc":"2.0","method":"turn/completed","params":{"turn":{"id":"turn_1","status":"failed"}}}),
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+        ],
+        _ => vec![],
+    });
+
+    let wait = |turn: &'static str| {
+        let client = client.clone();
+        tokio::spawn(async move {
+            client
+                .wait_turn_completed(Some(turn), std::time::Duration::from_secs(5))
+                .await
+        })
+    };
+    let first = wait("turn_1");
+    let second = wait("turn_2");
+
+    client.request("go", Value::Null).await.unwrap();
+    assert_eq!(first.await.unwrap().unwrap()["turn"]["status"], "failed");
+    assert_eq!(second.await.unwrap().unwrap()["turn"]["status"], "completed");
+}
+
+#[tokio::test]
+async fn parallel_requests_are_correlated_by_id() {
+    // Answer requests in reverse arrival order once both have arrived.
+    let mut held: Vec<Value> = Vec::new();
+    let client = spawn_stand_in_server(move |msg| {
+        held.push(json!({"jsonrpc":"2.0","id":msg["id"],"result":{"method":msg["method"]}}));
+        if held.len() == 2 {
+            held.drain(..).rev().collect()
+        } else {
+            vec![]
+        }
+    });
+
+    let (a, b) = tokio::join!(
+        client.request("thread/start", json!({})),
+        client.request("model/list", json!({}))
+    );
+    assert_eq!(a.unwrap()["method"], "thread/start");
+    assert_eq!(b.unwrap()["method"], "model/list");
+}
+
+// ============================================================================
+// Per-thread / per-turn agent output
+// ============================================================================
+
+#[tokio::test]
+async fn interleaved_turns_and_threads_keep_separate_outputs() {
+    let delta = |thread: &str, turn: &str, text: &str| {
+        json!({"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"threadId":thread,"turnId":turn,"itemId":"i","delta":text}})
+    };
+    let completed = |thread: &str, turn: &str| {
+        json!({"jsonrpc":"2.0","method":"turn/completed","params":{"threadId":thread,"turn":{"id":turn,"status":"completed"}}})
+    };
+    let client = spawn_stand_in_server(move |msg| match msg["method"].as_str() {
+        Some("go") => vec![
+            delta("thr_a", "turn_1", "A1-"),
+            delta("thr_b", "turn_1", "B1-"),
+            delta("thr_a", "turn_2", "A2-"),
+            delta("thr_a", "turn_1", "end"),
+            delta("thr_b", "turn_1", "end"),
+            completed("thr_a", "turn_1"),
+            completed("thr_b", "turn_1"),
+            completed("thr_a", "turn_2"),
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+        ],
+        _ => vec![],
+    });
+
+    client.request("go", Value::Null).await.unwrap();
+    assert_eq!(client.turn_output("thr_a", "turn_1").await.as_deref(), Some("A1-end"));
+    assert_eq!(client.turn_output("thr_b", "turn_1").await.as_deref(), Some("B1-end"));
+    assert_eq!(client.turn_output("thr_a", "turn_2").await.as_deref(), Some("A2-"));
+    assert_eq!(client.turn_output("thr_b", "turn_2").await, None);
+}
+
+#[tokio::test]
+async fn take_turn_output_releases_text() {
+    let client = spawn_stand_in_server(|msg| {
+        vec![
+            json!({"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"threadId":"thr_1","turnId":"turn_1","delta":"done"}}),
+            json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+        ]
+    });
+
+    client.request("go", Value::Null).await.unwrap();
+    assert_eq!(client.take_turn_output("thr_1", "turn_1").await.as_deref(), Some("done"));
+    assert_eq!(client.turn_output("thr_1", "turn_1").await, None);
+}
+
+// ============================================================================
+// Turn interruption
+// ============================================================================
+
+#[tokio::test]
+async fn turn_interrupt_sends_ids_and_turn_completes_interrupted() {
+    let (seen_tx, mut seen_rx) = tokio::sync::mpsc::unbounded_channel();
+    let client = spawn_stand_in_server(move |msg| match msg["method"].as_str() {
+        Some("turn/interrupt") => {
+            let _ = seen_tx.send(msg["params"].clone());
+            vec![
+                json!({"jsonrpc":"2.0","id":msg["id"],"result":{}}),
+                json!({"jsonrpc":"2.0","method":"turn/completed","params":{"threadId":"thr_1","turn":{"id":"turn_1","status":"interrupted"}}}),
+            ]
+        }
+        _ => vec![],
+    });
+
+    client.turn_interrupt("thr_1", "turn_1").await.unwrap();
+    let params = seen_rx.recv().await.unwrap();
+    assert_eq!(params["threadId"], "thr_1");
+    assert_eq!(params["turnId"], "turn_1");
+
+    let completed = client
+        .wait_turn_completed(Some("turn_1"), std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(completed["turn"]["status"], "interrupted");
+}
+
+// ============================================================================
+// ClientConfig — limits applied to a connected client
+// ============================================================================
+
+#[tokio::test]
+async fn configured_request_timeout_applies_to_request() {
+    let (transport, _server) = DuplexTransport::pair(1024);
+    let config = ClientConfig::new().request_timeout(std::time::Duration::from_millis(30));
+    let client = CodexAppServerClient::connect_with_config(transport, &config);
+
+    let err = client.request("thread/start", json!({})).await.unwrap_err();
+    match err {
+        AppServerError::Timeout { after, .. } => {
+            assert_eq!(after, std::time::Duration::from_millis(30))
+        }
+        other => panic!("Expected Timeout, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn configured_max_agent_text_bytes_caps_turn_output() {
+    let (transport, server) = DuplexTransport::pair(64 * 1024);
+    let config = ClientConfig::new().max_agent_text_bytes(4);
+    let client = CodexAppServerClient::connect_with_config(transport, &config);
+    tokio::spawn(async move {
+        let (read, mut write) = tokio::io::split(server);
+        let mut lines = BufReader::new(read).lines();
+        let req: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        for out in [
+            json!({"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"threadId":"t","turnId":"u","delta":"한글"}}),
+            json!({"jsonrpc":"2.0","id":req["id"],"result":{}}),
+        ] {
+            write.write_all(format!("{out}\n").as_bytes()).await.unwrap();
+        }
+        let _ = lines.next_line().await;
+    });
+
+    client.request("go", Value::Null).await.unwrap();
+    // 4 bytes fit only the first 3-byte character.
+    assert_eq!(client.turn_output("t", "u").await.as_deref(), Some("한"));
+}
+
+#[tokio::test]
+async fn spawn_with_missing_codex_bin_is_spawn_error() {
+    let config = ClientConfig::new().codex_bin("/nonexistent/codex-appserver-test-bin");
+    let err = CodexAppServerClient::spawn_with_config(&config)
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, AppServerError::Spawn(_)), "got: {err}");
+}
+
+// ============================================================================
+// App-server stderr capture
+// ============================================================================
+
+#[cfg(unix)]
+fn spawn_shell_server(script: &str, config: &ClientConfig) -> CodexAppServerClient {
+    let mut command = tokio::process::Command::new("sh");
+    command.arg("-c").arg(script);
+    let transport = codex_appserver::appserver::ChildStdioTransport::spawn(command).unwrap();
+    CodexAppServerClient::connect_with_config(transport, config)
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_exit_error_carries_stderr_tail() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("logs/server.stderr.log");
+    let config = ClientConfig::new().stderr_log(&log).echo_stderr(false);
+    let client = spawn_shell_server(
+        "read line; echo 'loading config' >&2; echo 'panicked: bad config' >&2; exit 3",
+        &config,
+    );
+
+    let err = client.request("initialize", json!({})).await.unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+    assert_eq!(
+        err.stderr_tail(),
+        Some("loading config\npanicked: bad config")
+    );
+    assert!(err.to_string().contains("panicked: bad config"));
+    assert_eq!(
+        std::fs::read_to_string(&log).unwrap(),
+        "loading config\npanicked: bad config\n"
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn request_timeout_carries_stderr_tail() {
+    let config = ClientConfig::new().echo_stderr(false);
+    let client = spawn_shell_server("echo 'rate limited, backing off' >&2; sleep 5", &config);
+
+    // Let the capture task pick the line up before the request times out.
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while client.stderr_tail(10).is_none() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("stderr line was not captured");
+    let err = client
+        .request_with_timeout("thread/start", json!({}), std::time::Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert!(err.is_timeout());
+    assert_eq!(err.stderr_tail(), Some("rate limited, backing off"));
+    assert_eq!(
+        client.stderr_tail(10).as_deref(),
+        Some("rate limited, backing off")
+    );
+}
+
+#[tokio::test]
+async fn transport_without_stderr_has_no_tail() {
+    let client = spawn_stand_in_server(|_| vec![]);
+    let err = client
+        .request_with_timeout("thread/start", json!({}), std::time::Duration::from_millis(20))
+        .await
+        .unwrap_err();
+    assert_eq!(err.stderr_tail(), None);
+    assert_eq!(client.stderr_tail(10), None);
+    assert!(!err.to_string().contains("stderr"));
+}
+
+// ============================================================================
+// Supervised client — respawn and thread resume
+// ============================================================================
+
+/// Stand-in server that answers the handshake and `thread/resume`, and "crashes"
+/// (closes the connection) when it receives `crash_on`.
+fn spawn_crashing_server(
+    crash_on: Option<&'static str>,
+    seen: tokio::sync::mpsc::UnboundedSender<Value>,
+) -> CodexAppServerClient {
+    let (transport, server) = DuplexTransport::pair(64 * 1024);
+    tokio::spawn(async move {
+        let (read, mut write) = tokio::io::split(server);
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let msg: Value = serde_json::from_str(&line).unwrap();
+            let _ = seen.send(msg.clone());
+            let method = msg["method"].as_str().unwrap_or("");
+            if Some(method) == crash_on {
+                return;
+            }
+            let out = match method {
+                "initialize" => vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":{}})],
+                "thread/resume" => vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":{"thread":{"id":msg["params"]["threadId"]}}})],
+                "turn/start" => vec![
+                    json!({"jsonrpc":"2.0","id":msg["id"],"result":{"turn":{"id":"turn_2"}}}),
+                    json!({"jsonrpc":"2.0","method":"turn/completed","params":{"turn":{"id":"turn_2","status":"completed"}}}),
+                ],
+                _ => vec![],
+            };
+            for out in out {
+                write.write_all(format!("{out}\n").as_bytes()).await.unwrap();
+            }
+        }
+    });
+    CodexAppServerClient::connect(transport)
+}
+
+#[tokio::test]
+async fn supervised_client_respawns_reinitializes_and_resumes_thread() {
+    let (seen_tx, mut seen_rx) = tokio::sync::mpsc::unbounded_channel();
+    let connects = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let counter = connects.clone();
+    let supervisor = SupervisedClient::with_connector(
+        move || {
+            // The first server crashes on turn/start; its replacement behaves.
+            let first = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0;
+            let client = spawn_crashing_server(first.then_some("turn/start"), seen_tx.clone());
+            async move { Ok(client) }
+        },
+        InitializeParams::new("test", "0"),
+    )
+    .await
+    .unwrap();
+
+    let err = supervisor
+        .client()
+        .await
+        .request("turn/start", json!({}))
+        .await
+        .unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+
+    let client = supervisor.reconnect().await.unwrap();
+    assert_eq!(supervisor.restarts().await, 1);
+    let resumed = supervisor
+        .resume_thread(
+            "thr_1",
+            &ThreadStartParams {
+                model: Some("gpt-5.4".to_string()),
+                ..ThreadStartParams::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(resumed.thread.id, "thr_1");
+
+    client.request("turn/start", json!({})).await.unwrap();
+    let completed = client
+        .wait_turn_completed(Some("turn_2"), std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(completed["turn"]["status"], "completed");
+
+    let mut methods = Vec::new();
+    while let Ok(msg) = seen_rx.try_recv() {
+        methods.push(msg["method"].as_str().unwrap_or("").to_string());
+        if msg["method"] == "thread/resume" {
+            assert_eq!(msg["params"]["threadId"], "thr_1");
+            assert_eq!(msg["params"]["model"], "gpt-5.4");
+        }
+    }
+    assert_eq!(
+        methods,
+        [
+            "initialize",
+            "initialized",
+            "turn/start",
+            "initialize",
+            "initialized",
+            "thread/resume",
+            "turn/start"
+        ]
+    );
+    assert_eq!(connects.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn supervised_reconnect_keeps_live_connection() {
+    let (seen_tx, _seen_rx) = tokio::sync::mpsc::unbounded_channel();
+    let connects = std::sync::Arc::new(std::sync::atomic::AtomicU32::new(0));
+    let counter = connects.clone();
+    let supervisor = SupervisedClient::with_connector(
+        move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let client = spawn_crashing_server(None, seen_tx.clone());
+            async move { Ok(client) }
+        },
+        InitializeParams::new("test", "0"),
+    )
+    .await
+    .unwrap();
+
+    supervisor.reconnect().await.unwrap();
+    assert_eq!(supervisor.restarts().await, 0);
+    assert_eq!(connects.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+// ============================================================================
+// Protocol transcripts — recording and replay
+// ============================================================================
+
+fn cassette(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cassettes")
+        .join(name)
+}
+
+#[tokio::test]
+async fn recorded_session_replays_identically() {
+    let dir = tempfile::tempdir().unwrap();
+    let transcript = dir.path().join("session.jsonl");
+
+    // Record a short session against a stand-in server.
+    let (transport, server) = DuplexTransport::pair(64 * 1024);
+    tokio::spawn(async move {
+        let (read, mut write) = tokio::io::split(server);
+        let mut lines = BufReader::new(read).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let msg: Value = serde_json::from_str(&line).unwrap();
+            let out = match msg["method"].as_str() {
+                Some("thread/start") => vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":{"thread":{"id":"thr_1"}}})],
+                Some("turn/start") => vec![
+                    json!({"jsonrpc":"2.0","id":msg["id"],"result":{"turn":{"id":"turn_1"}}}),
+                    json!({"jsonrpc":"2.0","method":"item/agentMessage/delta","params":{"threadId":"thr_1","turnId":"turn_1","delta":"recorded"}}),
+                    json!({"jsonrpc":"2.0","method":"turn/completed","params":{"threadId":"thr_1","turn":{"id":"turn_1","status":"completed"}}}),
+                ],
+                _ => vec![],
+            };
+            for out in out {
+                write.write_all(format!("{out}\n").as_bytes()).await.unwrap();
+            }
+        }
+    });
+    let config = ClientConfig::new().transcript(&transcript);
+    let client = CodexAppServerClient::connect_with_config(transport, &config);
+    client.request("thread/start", json!({})).await.unwrap();
+    client.request("turn/start", json!({"threadId":"thr_1"})).await.unwrap();
+    client
+        .wait_turn_completed(Some("turn_1"), std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    client.flush_transcript().await;
+    drop(client);
+
+    let records = read_transcript(&transcript).unwrap();
+    let dirs: Vec<Direction> = records.iter().map(|r| r.dir).collect();
+    assert_eq!(
+        dirs,
+        [
+            Direction::Send,
+            Direction::Recv,
+            Direction::Send,
+            Direction::Recv,
+            Direction::Recv,
+            Direction::Recv
+        ]
+    );
+
+    // Replay it with no server at all.
+    let client = CodexAppServerClient::connect(ReplayTransport::new(records));
+    let thread = client.request("thread/start", json!({})).await.unwrap();
+    assert_eq!(thread["thread"]["id"], "thr_1");
+    client.request("turn/start", json!({"threadId":"thr_1"})).await.unwrap();
+    let completed = client
+        .wait_turn_completed(Some("turn_1"), std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(completed["turn"]["status"], "completed");
+    assert_eq!(
+        client.turn_output("thr_1", "turn_1").await.as_deref(),
+        Some("recorded")
+    );
+}
+
+#[tokio::test]
+async fn replay_remaps_request_ids() {
+    let records = vec![
+        TranscriptRecord {
+            ts_ms: 0,
+            dir: Direction::Send,
+            line: r#"{"jsonrpc":"2.0","id":41,"method":"model/list"}"#.to_string(),
+        },
+        TranscriptRecord {
+            ts_ms: 1,
+            dir: Direction::Recv,
+            line: r#"{"id":41,"result":{"data":[]}}"#.to_string(),
+        },
+    ];
+    let client = CodexAppServerClient::connect(ReplayTransport::new(records));
+    // The client numbers this request 1; the recorded response said 41.
+    let result = client.request("model/list", Value::Null).await.unwrap();
+    assert_eq!(result["data"], json!([]));
+}
+
+#[tokio::test]
+async fn replay_ends_with_server_exit() {
+    let client = CodexAppServerClient::connect(ReplayTransport::new(Vec::new()));
+    let err = client.request("initialize", json!({})).await.unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+}
+
+#[tokio::test]
+async fn review_cassette_replays_full_session() {
+    let transport = ReplayTransport::from_file(cassette("review_turn.jsonl")).unwrap();
+    let client = CodexAppServerClient::connect(transport);
+
+    let init = client
+        .initialize(&InitializeParams::new("codex-appserver-review", "0.1.0"))
+        .await
+        .unwrap();
+    assert_eq!(init.user_agent.as_deref(), Some("codex_cli_rs/0.46.0"));
+    let thread_id = client
+        .thread_start(&ThreadStartParams::default())
+        .await
+        .unwrap()
+        .thread
+        .id;
+    let turn_id = client
+        .turn_start(&TurnStartParams::text(&thread_id, "Review the diff."))
+        .await
+        .unwrap()
+        .turn
+        .id;
+    let completed = client
+        .wait_for_turn(&turn_id, std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(completed.turn.status, TurnStatus::Completed);
+
+    let text = client.take_turn_output(&thread_id, &turn_id).await.unwrap();
+    let review: ReviewOutput = serde_json::from_str(&text).unwrap();
+    assert_eq!(review.score, 7);
+    assert_eq!(review.findings[0].line, Some(12));
+    assert!(client.shutdown().await.is_clean());
+}
+
+// ============================================================================
+// Typed method params and results
+// ============================================================================
+
+#[test]
+fn thread_start_params_serialize_camel_case_and_skip_unset() {
+    let params = ThreadStartParams {
+        model: Some("gpt-5.4".to_string()),
+        cwd: None,
+        sandbox: Some(SandboxMode::ReadOnly),
+        approval_policy: Some(ApprovalPolicy::Never),
+    };
+    assert_eq!(
+        serde_json::to_value(&params).unwrap(),
+        json!({"model":"gpt-5.4","sandbox":"read-only","approvalPolicy":"never"})
+    );
+}
+
+#[test]
+fn turn_start_params_text_input_and_schema() {
+    let params = TurnStartParams {
+        output_schema: Some(review_output_schema()),
+        ..TurnStartParams::text("thr_1", "Review this.")
+    };
+    let value = serde_json::to_value(&params).unwrap();
+    assert_eq!(value["threadId"], "thr_1");
+    assert_eq!(value["input"], json!([{"type":"text","text":"Review this."}]));
+    assert_eq!(value["outputSchema"]["type"], "object");
+}
+
+#[test]
+fn turn_completed_params_decode_status_and_error() {
+    let failed: TurnCompletedParams = serde_json::from_value(json!({
+        "threadId": "thr_1",
+        "turn": {"id": "turn_1", "status": "failed", "error": {"message": "quota exceeded", "codexErrorInfo": "usageLimitExceeded"}}
+    }))
+    .unwrap();
+    assert_eq!(failed.turn.status, TurnStatus::Failed);
+    assert_eq!(failed.turn.error.unwrap().message, "quota exceeded");
+
+    let future: TurnCompletedParams =
+        serde_json::from_value(json!({"turn": {"id": "turn_2", "status": "paused"}})).unwrap();
+    assert_eq!(future.turn.status, TurnStatus::Unknown);
+    assert_eq!(future.thread_id, None);
+}
+
+#[tokio::test]
+async fn typed_call_with_unexpected_result_is_protocol_error() {
+    let client = spawn_stand_in_server(|msg| match msg["method"].as_str() {
+        // An older server shape: the id at the top level instead of under `thread`.
+        Some("thread/start") => vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":{"id":"thr_1"}})],
+        _ => vec![],
+    });
+
+    let err = client
+        .thread_start(&ThreadStartParams::default())
+        .await
+        .unwrap_err();
+    match err {
+        AppServerError::Protocol(msg) => assert!(msg.contains("thread/start"), "{msg}"),
+        other => panic!("Expected Protocol, got {other:?}"),
+    }
+}
+
+// ============================================================================
+// Retry with backoff for transient JSON-RPC errors
+// ============================================================================
+
+/// Fast retries, so the tests do not sleep for the default backoff.
+fn fast_retry_config(attempts: u32) -> ClientConfig {
+    ClientConfig::new().retry_policy(
+        RetryPolicy::new()
+            .max_attempts(attempts)
+            .initial_backoff(std::time::Duration::from_millis(1))
+            .jitter(0.0),
+    )
+}
+
+/// Answer `method` with a backpressure error `failures` times, then with `result`.
+fn flaky_responder(
+    method: &'static str,
+    failures: usize,
+    result: Value,
+    calls: Arc<AtomicUsize>,
+) -> impl FnMut(&Value) -> Vec<Value> + Send + 'static {
+    move |msg| {
+        if msg["method"] != method {
+            return vec![];
+        }
+        if calls.fetch_add(1, Ordering::SeqCst) < failures {
+            vec![json!({"jsonrpc":"2.0","id":msg["id"],"error":{"code":-32001,"message":"Server overloaded"}})]
+        } else {
+            vec![json!({"jsonrpc":"2.0","id":msg["id"],"result":result})]
+        }
+    }
+}
+
+#[tokio::test]
+async fn model_list_retries_backpressure_then_succeeds() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let result = json!({"data":[{"id":"gpt-5.4","displayName":"GPT-5.4","isDefault":true}],"nextCursor":null});
+    let client = spawn_stand_in_server_with_config(
+        &fast_retry_config(4),
+        flaky_responder("model/list", 2, result, calls.clone()),
+    );
+
+    let models = client.model_list(&ModelListParams::default()).await.unwrap();
+    assert_eq!(models.data[0].id, "gpt-5.4");
+    assert!(models.data[0].is_default);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn exhausted_retries_keep_the_last_error() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let client = spawn_stand_in_server_with_config(
+        &fast_retry_config(3),
+        flaky_responder("thread/start", usize::MAX, json!({}), calls.clone()),
+    );
+
+    let err = client
+        .thread_start(&ThreadStartParams::default())
+        .await
+        .unwrap_err();
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert!(err.is_backpressure());
+    assert!(matches!(err, AppServerError::RetriesExhausted { attempts: 3, .. }));
+    assert!(matches!(err.root(), AppServerError::Rpc { .. }));
+    assert_eq!(
+        err.to_string(),
+        "Gave up after 3 attempts: Request 'thread/start' failed: JSON-RPC error -32001: Server overloaded"
+    );
+}
+
+#[tokio::test]
+async fn non_retryable_errors_and_raw_requests_fail_immediately() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let client = spawn_stand_in_server_with_config(&fast_retry_config(4), move |msg| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        vec![json!({"jsonrpc":"2.0","id":msg["id"],"error":{"code":-32600,"message":"bad params"}})]
+    });
+    let err = client
+        .initialize(&InitializeParams::new("test", "0"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AppServerError::Rpc { .. }));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    // Backpressure on a request outside the idempotent set is not retried.
+    let calls = Arc::new(AtomicUsize::new(0));
+    let client = spawn_stand_in_server_with_config(
+        &fast_retry_config(4),
+        flaky_responder("turn/start", usize::MAX, json!({}), calls.clone()),
+    );
+    let err = client.request("turn/start", json!({})).await.unwrap_err();
+    assert!(err.is_backpressure());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
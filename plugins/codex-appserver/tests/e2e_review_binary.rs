@@ -0,0 +1,769 @@
+//! End-to-end tests against the `fake-codex-appserver` test binary.
+//! Exercises process spawning, the reader task, timeouts, shutdown, and the full
+//! `codex-appserver-review` binary — without codex or network access.
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use codex_appserver::appserver::protocol::InitializeParams;
+use codex_appserver::appserver::{
+    ChildStdioTransport, ClientConfig, CodexAppServerClient, OUTPUT_SCHEMA_MIN_VERSION,
+};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+const FAKE_BIN: &str = env!("CARGO_BIN_EXE_fake-codex-appserver");
+const REVIEW_BIN: &str = env!("CARGO_BIN_EXE_codex-appserver-review");
+
+/// Structured review the fake agent streams back.
+fn review_json() -> String {
+    json!({
+        "findings": [{
+            "severity": "HIGH",
+            "dimension": "Bugs",
+            "title": "Unchecked index",
+            "file": "src/main.rs",
+            "line": 42,
+            "problem": "Indexing may panic on empty input.",
+            "suggestion": "Use get() and handle None."
+        }],
+        "score": 7,
+        "summary": "One panic path.",
+        "strengths": ["Clear structure"]
+    })
+    .to_string()
+}
+
+/// `turn/start` step that streams `text` in two deltas and completes with `status`.
+fn turn_step(text: &str, status: &str, error: Option<&str>) -> Value {
+    let (head, tail) = text.split_at(text.len() / 2);
+    let mut turn = json!({ "id": "turn_1", "status": status });
+    if let Some(message) = error {
+        turn["error"] = json!({ "message": message });
+    }
+    json!({
+        "result": { "turn": { "id": "turn_1", "status": "inProgress" } },
+        "messages": [
+            { "method": "turn/started", "params": { "threadId": "thr_1", "turn": { "id": "turn_1" } } },
+            { "method": "item/agentMessage/delta", "params": { "threadId": "thr_1", "turnId": "turn_1", "delta": head } },
+            { "method": "item/agentMessage/delta", "params": { "threadId": "thr_1", "turnId": "turn_1", "delta": tail } },
+            { "method": "turn/completed", "params": { "threadId": "thr_1", "turn": turn } }
+        ]
+    })
+}
+
+/// Scenario for a review that succeeds.
+fn review_scenario() -> Value {
+    json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+            "turn/start": turn_step(&review_json(), "completed", None)
+        }
+    })
+}
+
+struct ReviewRun {
+    output: Output,
+    dir: TempDir,
+}
+
+impl ReviewRun {
+    fn code(&self) -> Option<i32> {
+        self.output.status.code()
+    }
+
+    fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.output.stdout).into_owned()
+    }
+
+    fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.output.stderr).into_owned()
+    }
+
+    fn project(&self) -> PathBuf {
+        self.dir.path().join("project")
+    }
+}
+
+fn write_scenario(dir: &Path, scenario: &Value) -> PathBuf {
+    let path = dir.join("scenario.json");
+    std::fs::write(&path, scenario.to_string()).unwrap();
+    path
+}
+
+/// Run the review binary against the fake server with `scenario`.
+fn run_review(scenario: Value, env: &[(&str, &str)]) -> ReviewRun {
+    run_review_with_args(scenario, &[], env)
+}
+
+/// Like [`run_review`], passing extra command-line flags.
+fn run_review_with_args(scenario: Value, args: &[&str], env: &[(&str, &str)]) -> ReviewRun {
+    let dir = tempfile::tempdir().unwrap();
+    let mut command = review_command(dir.path(), &scenario, args);
+    for (key, value) in env {
+        command.env(key, value);
+    }
+    let output = command.output().unwrap();
+    ReviewRun { output, dir }
+}
+
+/// The review binary set up in `dir` to run against the fake server with `scenario`.
+fn review_command(dir: &Path, scenario: &Value, args: &[&str]) -> std::process::Command {
+    let project = dir.join("project");
+    std::fs::create_dir(&project).unwrap();
+    let prompt = dir.join("prompt.txt");
+    std::fs::write(&prompt, "Review the diff.").unwrap();
+    let scenario = write_scenario(dir, scenario);
+
+    let mut command = std::process::Command::new(REVIEW_BIN);
+    command
+        .arg("--project-path")
+        .arg(&project)
+        .args(args)
+        .arg("session-1")
+        .arg(&prompt)
+        .env("CODEX_BIN", FAKE_BIN)
+        .env("FAKE_CODEX_SCENARIO", &scenario)
+        .env_remove("CODEX_PRICE_TABLE")
+        .env_remove("CODEX_TURN_TIMEOUT")
+        .env_remove("CODEX_TURN_RETRIES")
+        .env_remove("CODEX_STALL_WARN")
+        .env_remove("CODEX_STALL_TIMEOUT");
+    command
+}
+
+/// Connect a library client to the fake server running `scenario`.
+fn spawn_fake(dir: &Path, scenario: Value, config: ClientConfig) -> CodexAppServerClient {
+    let scenario = write_scenario(dir, &scenario);
+    let config = config.codex_bin(FAKE_BIN).echo_stderr(false);
+    let mut command = config.command();
+    command.env("FAKE_CODEX_SCENARIO", scenario);
+    let transport = ChildStdioTransport::spawn(command).unwrap();
+    CodexAppServerClient::connect_with_config(transport, &config)
+}
+
+// ============================================================================
+// Library client against a spawned process
+// ============================================================================
+
+#[tokio::test]
+async fn spawned_server_handshake_and_clean_shutdown() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = spawn_fake(dir.path(), json!({}), ClientConfig::new());
+
+    let result = client
+        .initialize(&InitializeParams::new("test", "0"))
+        .await
+        .unwrap();
+    assert!(result
+        .user_agent
+        .unwrap()
+        .starts_with("fake-codex-appserver/"));
+
+    let status = client.shutdown().await;
+    assert!(status.is_clean(), "{status:?}");
+}
+
+#[tokio::test]
+async fn spawned_server_info_is_kept_after_handshake() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = spawn_fake(dir.path(), json!({}), ClientConfig::new());
+    assert!(client.server_info().is_none());
+
+    client
+        .initialize(&InitializeParams::new("test", "0"))
+        .await
+        .unwrap();
+    let info = client.server_info().unwrap();
+    assert_eq!(info.server_version(), Some(OUTPUT_SCHEMA_MIN_VERSION));
+    assert_eq!(info.server_info.unwrap().name, "fake-codex-appserver");
+}
+
+#[tokio::test]
+async fn spawned_server_turn_streams_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = spawn_fake(dir.path(), review_scenario(), ClientConfig::new());
+
+    client
+        .initialize(&InitializeParams::new("test", "0"))
+        .await
+        .unwrap();
+    client.request("turn/start", json!({})).await.unwrap();
+    let completed = client
+        .wait_turn_completed(Some("turn_1"), std::time::Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(completed["turn"]["status"], "completed");
+    assert_eq!(
+        client.take_turn_output("thr_1", "turn_1").await.unwrap(),
+        review_json()
+    );
+}
+
+#[tokio::test]
+async fn spawned_server_hang_times_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = json!({ "methods": { "thread/start": { "hang": true } } });
+    let config = ClientConfig::new().request_timeout(std::time::Duration::from_millis(200));
+    let client = spawn_fake(dir.path(), scenario, config);
+
+    let err = client.request("thread/start", json!({})).await.unwrap_err();
+    assert!(err.is_timeout(), "got: {err}");
+}
+
+#[tokio::test]
+async fn spawned_server_crash_reports_exit_status_and_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = json!({
+        "methods": { "thread/start": { "stderr": ["fatal: config.toml is invalid"], "exit": 3 } }
+    });
+    let client = spawn_fake(dir.path(), scenario, ClientConfig::new());
+
+    let err = client.request("thread/start", json!({})).await.unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+    assert_eq!(err.stderr_tail(), Some("fatal: config.toml is invalid"));
+}
+
+#[tokio::test]
+async fn request_after_server_exit_fails_fast_with_server_exited() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = json!({ "methods": { "turn/start": { "exit": 101 } } });
+    let config = ClientConfig::new().request_timeout(std::time::Duration::from_secs(30));
+    let client = spawn_fake(dir.path(), scenario, config);
+
+    let err = client.request("turn/start", json!({})).await.unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+    assert!(client.is_closed());
+
+    let started = std::time::Instant::now();
+    let err = client.request("turn/start", json!({})).await.unwrap_err();
+    assert!(err.is_server_exited(), "got: {err}");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+// ============================================================================
+// codex-appserver-review binary, end to end
+// ============================================================================
+
+#[test]
+fn review_binary_completes_and_saves_report() {
+    let run = run_review(review_scenario(), &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(run.stdout().contains("**Score**: 7/10"));
+
+    let reviews = run.project().join(".codex-review-cache/reviews");
+    let saved: Value =
+        serde_json::from_str(&std::fs::read_to_string(reviews.join("session-1.json")).unwrap())
+            .unwrap();
+    assert_eq!(saved["findings"][0]["line"], 42);
+    assert!(std::fs::read_to_string(reviews.join("session-1.md"))
+        .unwrap()
+        .contains("Unchecked index"));
+}
+
+/// Review scenario whose turn reads `src/main.rs` with a command and reasons about it.
+fn command_scenario() -> Value {
+    let command = json!({
+        "id": "cmd_1", "type": "commandExecution", "command": "cat src/main.rs", "cwd": "/repo",
+        "status": "completed", "exitCode": 0, "aggregatedOutput": "fn main() {}\n",
+        "commandActions": [{ "type": "read", "command": "cat src/main.rs", "name": "main.rs", "path": "src/main.rs" }]
+    });
+    let items = [
+        json!({ "method": "item/started", "params": { "threadId": "thr_1", "turnId": "turn_1",
+            "item": { "id": "cmd_1", "type": "commandExecution", "command": "cat src/main.rs", "status": "inProgress" } } }),
+        json!({ "method": "item/completed", "params": { "threadId": "thr_1", "turnId": "turn_1", "item": command } }),
+        json!({ "method": "item/completed", "params": { "threadId": "thr_1", "turnId": "turn_1",
+            "item": { "id": "rs_1", "type": "reasoning", "summary": ["Looking at indexing"], "content": [] } } }),
+    ];
+    let mut scenario = review_scenario();
+    let messages = scenario["methods"]["turn/start"]["messages"]
+        .as_array_mut()
+        .unwrap();
+    for (i, item) in items.into_iter().enumerate() {
+        messages.insert(1 + i, item);
+    }
+    scenario
+}
+
+#[test]
+fn review_binary_writes_sarif_report() {
+    let run = run_review_with_args(review_scenario(), &["--format", "sarif"], &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let path = run
+        .project()
+        .join(".codex-review-cache/reviews/session-1.sarif");
+    let sarif: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "bugs");
+    assert_eq!(result["level"], "error");
+    assert_eq!(
+        result["locations"][0]["physicalLocation"]["region"]["startLine"],
+        42
+    );
+}
+
+#[test]
+fn review_binary_writes_junit_and_checkstyle_reports() {
+    let args = ["--format", "junit,checkstyle", "--junit-fail-on", "critical"];
+    let run = run_review_with_args(review_scenario(), &args, &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let reviews = run.project().join(".codex-review-cache/reviews");
+
+    let junit = std::fs::read_to_string(reviews.join("session-1.junit.xml")).unwrap();
+    // The HIGH finding is below the critical threshold, so the case passes.
+    assert!(junit.contains("tests=\"1\" failures=\"0\""), "{junit}");
+    assert!(junit.contains("<testcase classname=\"codex-review\" name=\"src/main.rs\""));
+
+    let checkstyle = std::fs::read_to_string(reviews.join("session-1.checkstyle.xml")).unwrap();
+    assert!(checkstyle.contains("<file name=\"src/main.rs\">"), "{checkstyle}");
+    assert!(checkstyle.contains("<error line=\"42\" severity=\"error\""));
+}
+
+#[test]
+fn review_binary_writes_gitlab_code_quality_report() {
+    let run = run_review_with_args(review_scenario(), &["--format=codequality"], &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let path = run
+        .project()
+        .join(".codex-review-cache/reviews/session-1.codequality.json");
+    let issues: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    let issue = &issues[0];
+    assert_eq!(issue["description"], "Unchecked index");
+    assert_eq!(issue["severity"], "major");
+    assert_eq!(issue["location"]["path"], "src/main.rs");
+    assert_eq!(issue["location"]["lines"]["begin"], 42);
+    assert!(issue["fingerprint"].as_str().is_some_and(|fp| !fp.is_empty()));
+}
+
+#[test]
+fn review_binary_writes_reviewdog_reports() {
+    let run = run_review_with_args(review_scenario(), &["--format", "rdjson,rdjsonl"], &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let reviews = run.project().join(".codex-review-cache/reviews");
+
+    let result: Value =
+        serde_json::from_str(&std::fs::read_to_string(reviews.join("session-1.rdjson")).unwrap())
+            .unwrap();
+    let diagnostic = &result["diagnostics"][0];
+    assert_eq!(diagnostic["severity"], "ERROR");
+    assert_eq!(diagnostic["location"]["path"], "src/main.rs");
+    assert_eq!(diagnostic["location"]["range"]["start"]["line"], 42);
+    assert!(diagnostic.get("suggestions").is_none());
+    assert!(diagnostic["message"]
+        .as_str()
+        .unwrap()
+        .ends_with("Suggestion: Use get() and handle None."));
+
+    let lines = std::fs::read_to_string(reviews.join("session-1.rdjsonl")).unwrap();
+    let first: Value = serde_json::from_str(lines.lines().next().unwrap()).unwrap();
+    assert_eq!(&first, diagnostic);
+}
+
+#[test]
+fn review_binary_always_writes_html_report() {
+    let run = run_review(review_scenario(), &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let html =
+        std::fs::read_to_string(run.project().join(".codex-review-cache/reviews/session-1.html"))
+            .unwrap();
+    assert!(html.contains("<title>Code Review: session-1</title>"));
+    assert!(html.contains("Unchecked index"));
+    assert!(html.contains("<code>src/main.rs:42</code>"));
+    assert!(run.stdout().contains("**HTML report**: "));
+}
+
+#[test]
+fn review_binary_saves_turn_transcript() {
+    let run = run_review(command_scenario(), &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let path = run
+        .project()
+        .join(".codex-review-cache/reviews/session-1.transcript.json");
+    let transcript: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(transcript["turn_id"], "turn_1");
+    assert_eq!(transcript["files_read"], json!(["src/main.rs"]));
+    let items = transcript["items"].as_array().unwrap();
+    assert_eq!(items[0]["type"], "command_execution");
+    assert_eq!(items[0]["exit_code"], 0);
+    assert_eq!(items[0]["output"], "fn main() {}\n");
+    assert_eq!(items[1]["summary"], "Looking at indexing");
+}
+
+#[test]
+fn review_binary_plain_progress_lists_commands_and_files() {
+    let run = run_review_with_args(command_scenario(), &["--progress=plain"], &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let stderr = run.stderr();
+    assert!(stderr.contains("[00:00] $ cat src/main.rs"), "{stderr}");
+    assert!(stderr.contains("[00:00] read src/main.rs"), "{stderr}");
+    assert!(stderr.contains("turn finished: 1 command, 1 file read, 0 tokens"), "{stderr}");
+    assert!(!stderr.contains('\x1b'), "plain progress must not use escape codes");
+}
+
+#[test]
+fn review_binary_quiet_progress_and_bad_mode() {
+    let run = run_review_with_args(command_scenario(), &["--progress", "quiet"], &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(!run.stderr().contains("$ cat src/main.rs"));
+    assert!(!run.stderr().contains("turn finished"));
+
+    let run = run_review_with_args(review_scenario(), &["--progress=loud"], &[]);
+    assert_eq!(run.code(), Some(2));
+    assert!(run.stderr().contains("--progress must be plain, quiet or rich"));
+}
+
+#[test]
+fn review_binary_reports_usage_and_cost() {
+    let usage = |input: u64, cached: u64, output: u64| {
+        json!({ "inputTokens": input, "cachedInputTokens": cached, "outputTokens": output,
+                "reasoningOutputTokens": 0, "totalTokens": input + output })
+    };
+    let mut scenario = review_scenario();
+    let messages = scenario["methods"]["turn/start"]["messages"]
+        .as_array_mut()
+        .unwrap();
+    messages.insert(
+        1,
+        json!({ "method": "thread/tokenUsage/updated", "params": { "threadId": "thr_1", "turnId": "turn_1",
+            "tokenUsage": { "total": usage(1_000_000, 200_000, 50_000), "last": usage(1_000_000, 200_000, 50_000) } } }),
+    );
+    let dir = tempfile::tempdir().unwrap();
+    let prices = dir.path().join("prices.json");
+    std::fs::write(
+        &prices,
+        json!({ "gpt-5.4": { "input": 2.0, "cached_input": 0.5, "output": 10.0 } }).to_string(),
+    )
+    .unwrap();
+
+    let run = run_review(scenario, &[("CODEX_PRICE_TABLE", prices.to_str().unwrap())]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    // 800k * $2 + 200k * $0.50 + 50k * $10 per million tokens.
+    assert!(run.stdout().contains("**Estimated cost**: $2.2000 (gpt-5.4)"), "{}", run.stdout());
+    assert!(run.stdout().contains("1000000 input (200000 cached), 50000 output"));
+
+    let cache = run.project().join(".codex-review-cache");
+    let saved: Value = serde_json::from_str(
+        &std::fs::read_to_string(cache.join("reviews/session-1.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(saved["score"], 7);
+    assert_eq!(saved["usage"]["tokens"]["outputTokens"], 50_000);
+    assert_eq!(saved["usage"]["cost_usd"], 2.2);
+
+    let ledger = std::fs::read_to_string(cache.join("usage.jsonl")).unwrap();
+    let record: Value = serde_json::from_str(ledger.lines().next().unwrap()).unwrap();
+    assert_eq!(record["session"], "session-1");
+    assert_eq!(record["outcome"], "completed");
+    assert_eq!(record["cost_usd"], 2.2);
+}
+
+/// Scenario whose turn streams `text`, reports `usage` and then never completes
+/// unless interrupted.
+fn runaway_scenario(text: &str, usage: Value) -> Value {
+    json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+            "turn/start": {
+                "result": { "turn": { "id": "turn_1", "status": "inProgress" } },
+                "messages": [
+                    { "method": "turn/started", "params": { "threadId": "thr_1", "turn": { "id": "turn_1" } } },
+                    { "method": "item/agentMessage/delta", "params": { "threadId": "thr_1", "turnId": "turn_1", "delta": text } },
+                    { "method": "thread/tokenUsage/updated", "params": { "threadId": "thr_1", "turnId": "turn_1",
+                        "tokenUsage": { "total": usage, "last": usage } } }
+                ]
+            },
+            "turn/interrupt": {
+                "result": {},
+                "messages": [
+                    { "method": "turn/completed", "params": { "threadId": "thr_1", "turn": { "id": "turn_1", "status": "interrupted" } } }
+                ]
+            }
+        }
+    })
+}
+
+#[test]
+fn review_binary_token_budget_interrupts_and_salvages_review() {
+    let usage = json!({ "inputTokens": 40_000, "outputTokens": 12_000, "totalTokens": 52_000 });
+    let scenario = runaway_scenario(&review_json(), usage);
+    let run = run_review_with_args(scenario, &["--max-tokens", "50000"], &[]);
+    assert_eq!(run.code(), Some(7), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("token budget exceeded (52000 tokens used, limit 50000)"));
+
+    let cache = run.project().join(".codex-review-cache");
+    let saved: Value = serde_json::from_str(
+        &std::fs::read_to_string(cache.join("reviews/session-1.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(saved["score"], 7);
+    assert_eq!(saved["budget_exceeded"]["limit"], "tokens");
+    assert_eq!(saved["budget_exceeded"]["used_tokens"], 52_000);
+    let md = std::fs::read_to_string(cache.join("reviews/session-1.md")).unwrap();
+    assert!(md.contains("**Stopped early**"));
+    assert!(md.contains("Unchecked index"));
+
+    let ledger = std::fs::read_to_string(cache.join("usage.jsonl")).unwrap();
+    let record: Value = serde_json::from_str(ledger.lines().next().unwrap()).unwrap();
+    assert_eq!(record["outcome"], "budget_exceeded");
+}
+
+#[test]
+fn review_binary_cost_budget_without_review_points_to_partial_output() {
+    let usage = json!({ "inputTokens": 1_000_000, "outputTokens": 0, "totalTokens": 1_000_000 });
+    let scenario = runaway_scenario("Still reading files...", usage);
+    let dir = tempfile::tempdir().unwrap();
+    let prices = dir.path().join("prices.json");
+    std::fs::write(
+        &prices,
+        json!({ "gpt-5.4": { "input": 2.0, "cached_input": 0.5, "output": 10.0 } }).to_string(),
+    )
+    .unwrap();
+
+    let run = run_review_with_args(
+        scenario,
+        &["--max-cost", "1.50"],
+        &[("CODEX_PRICE_TABLE", prices.to_str().unwrap())],
+    );
+    assert_eq!(run.code(), Some(7), "stderr:\n{}", run.stderr());
+
+    let reviews = run.project().join(".codex-review-cache/reviews");
+    let saved: Value =
+        serde_json::from_str(&std::fs::read_to_string(reviews.join("session-1.json")).unwrap())
+            .unwrap();
+    assert!(saved.get("score").is_none());
+    assert_eq!(saved["budget_exceeded"]["limit"], "cost");
+    assert_eq!(saved["budget_exceeded"]["used_cost_usd"], 2.0);
+    let md = std::fs::read_to_string(reviews.join("session-1.md")).unwrap();
+    assert!(md.contains("session-1.partial.txt"));
+    assert_eq!(
+        std::fs::read_to_string(reviews.join("session-1.partial.txt")).unwrap(),
+        "Still reading files..."
+    );
+}
+
+#[test]
+fn review_binary_cost_budget_runs_with_builtin_price_of_default_model() {
+    let run = run_review_with_args(review_scenario(), &["--max-cost", "5"], &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    let saved: Value = serde_json::from_str(
+        &std::fs::read_to_string(run.project().join(".codex-review-cache/reviews/session-1.json"))
+            .unwrap(),
+    )
+    .unwrap();
+    assert!(saved["usage"]["cost_usd"].is_number(), "{saved}");
+}
+
+#[test]
+fn review_binary_cost_budget_needs_a_model_price() {
+    let args = ["--max-cost", "1", "--model", "mystery"];
+    let run = run_review_with_args(review_scenario(), &args, &[]);
+    assert_eq!(run.code(), Some(2));
+    assert!(run.stderr().contains("CODEX_PRICE_TABLE"));
+}
+
+#[test]
+fn review_binary_stalled_turn_is_interrupted_with_exit_8() {
+    let mut scenario = runaway_scenario("Looking at", json!({ "totalTokens": 100 }));
+    scenario["methods"]["turn/start"]["messages"]
+        .as_array_mut()
+        .unwrap()
+        .truncate(2);
+    let run = run_review(
+        scenario,
+        &[("CODEX_STALL_WARN", "0"), ("CODEX_STALL_TIMEOUT", "1")],
+    );
+    assert_eq!(run.code(), Some(8), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("App server stalled: no messages for 1s during turn turn_1"));
+    assert!(!run.stderr().contains("may be stuck"));
+
+    let cache = run.project().join(".codex-review-cache");
+    assert_eq!(
+        std::fs::read_to_string(cache.join("reviews/session-1.partial.txt")).unwrap(),
+        "Looking at"
+    );
+    let ledger = std::fs::read_to_string(cache.join("usage.jsonl")).unwrap();
+    let record: Value = serde_json::from_str(ledger.lines().next().unwrap()).unwrap();
+    assert_eq!(record["outcome"], "stalled");
+}
+
+/// Poll `check` every 20ms until it holds, failing the test after 10s.
+fn wait_for(what: &str, mut check: impl FnMut() -> bool) {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !check() {
+        assert!(std::time::Instant::now() < deadline, "timed out waiting for {what}");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: &str) -> bool {
+    std::process::Command::new("kill")
+        .arg(format!("-{signal}"))
+        .arg(pid.to_string())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .unwrap()
+        .success()
+}
+
+#[test]
+#[cfg(unix)]
+fn review_binary_interrupted_during_startup_stops_the_server() {
+    for (signal, code) in [("INT", 130), ("TERM", 143)] {
+        let dir = tempfile::tempdir().unwrap();
+        let scenario = json!({
+            "methods": {
+                "thread/start": { "stderr": ["creating thread"], "hang": true }
+            }
+        });
+        let mut child = review_command(dir.path(), &scenario, &[])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // Interrupt while `thread/start` is outstanding.
+        let log = dir.path().join("project/.codex-review-cache/logs/session-1.stderr.log");
+        wait_for("thread/start", || {
+            std::fs::read_to_string(&log).is_ok_and(|log| log.contains("creating thread"))
+        });
+        assert!(send_signal(child.id(), signal));
+        wait_for("the review to exit", || child.try_wait().unwrap().is_some());
+        let output = child.wait_with_output().unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(code), "stderr:\n{stderr}");
+        assert!(stderr.contains(&format!("Received SIG{signal} during startup")));
+
+        let server = std::fs::read_to_string(dir.path().join("scenario.json.pid")).unwrap();
+        let server: u32 = server.trim().parse().unwrap();
+        wait_for("the server to exit", || !send_signal(server, "0"));
+    }
+}
+
+#[test]
+fn review_binary_warns_about_a_quiet_turn_that_then_completes() {
+    let mut scenario = review_scenario();
+    scenario["methods"]["turn/start"]["messages_delay_ms"] = json!(1_500);
+    let run = run_review(scenario, &[("CODEX_STALL_WARN", "1")]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(run
+        .stderr()
+        .contains("Warning: no messages from the app server for 1s; the turn may be stuck"));
+}
+
+#[test]
+fn review_binary_failed_turn_exits_1() {
+    let scenario = json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+            "turn/start": turn_step("", "failed", Some("model refused"))
+        }
+    });
+    let run = run_review(scenario, &[]);
+    assert_eq!(run.code(), Some(1));
+    assert!(run.stderr().contains("Turn failed: model refused"));
+}
+
+#[test]
+fn review_binary_rpc_error_exits_4() {
+    let scenario = json!({
+        "methods": {
+            "thread/start": { "error": { "code": -32600, "message": "unknown model" } }
+        }
+    });
+    let run = run_review(scenario, &[]);
+    assert_eq!(run.code(), Some(4));
+    assert!(run.stderr().contains("unknown model"));
+}
+
+#[test]
+fn review_binary_retries_thread_start_under_backpressure() {
+    let mut scenario = review_scenario();
+    scenario["methods"]["thread/start"] = json!([
+        { "error": { "code": -32001, "message": "Server overloaded" } },
+        { "result": { "thread": { "id": "thr_1" } } }
+    ]);
+    let run = run_review(scenario, &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("[appserver-retry]"));
+    assert!(run.stderr().contains("Server overloaded; retrying in"));
+}
+
+#[test]
+fn review_binary_turn_timeout_exits_3() {
+    let scenario = json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+            "turn/start": { "result": { "turn": { "id": "turn_1", "status": "inProgress" } } }
+        }
+    });
+    let run = run_review(scenario, &[("CODEX_TURN_TIMEOUT", "1")]);
+    assert_eq!(run.code(), Some(3), "stderr:\n{}", run.stderr());
+}
+
+#[test]
+fn review_binary_retries_turn_after_crash() {
+    let crash = json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+            "turn/start": { "stderr": ["fatal: stream reset"], "exit": 101 }
+        }
+    });
+    let run = run_review(json!({ "runs": [crash, review_scenario()] }), &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("retrying the turn (1/1)"));
+
+    let log = run
+        .project()
+        .join(".codex-review-cache/logs/session-1.stderr.log");
+    assert!(std::fs::read_to_string(log)
+        .unwrap()
+        .contains("fatal: stream reset"));
+}
+
+#[test]
+fn review_binary_retries_turn_when_server_dies_before_turn_start() {
+    let crash = json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } }, "exit_after": 101 }
+        }
+    });
+    let run = run_review(json!({ "runs": [crash, review_scenario()] }), &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("retrying the turn (1/1)"));
+}
+
+#[test]
+fn review_binary_refuses_old_codex_with_exit_6() {
+    let scenario = json!({
+        "methods": {
+            "initialize": { "result": { "userAgent": "codex_cli_rs/0.30.0 (Linux; x86_64)" } }
+        }
+    });
+    let run = run_review(scenario, &[]);
+    assert_eq!(run.code(), Some(6), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("codex 0.30.0 is not supported"));
+}
+
+#[test]
+fn review_binary_without_output_schema_support_still_reviews() {
+    let mut scenario = review_scenario();
+    scenario["methods"]["initialize"] =
+        json!({ "result": { "userAgent": "codex_cli_rs/0.46.0 (Linux; x86_64)" } });
+    let run = run_review(scenario, &[]);
+    assert_eq!(run.code(), Some(0), "stderr:\n{}", run.stderr());
+    assert!(run.stderr().contains("does not support outputSchema"));
+}
+
+#[test]
+fn review_binary_crash_without_retries_exits_5_with_stderr() {
+    let crash = json!({
+        "methods": {
+            "thread/start": { "result": { "thread": { "id": "thr_1" } } },
+            "turn/start": { "stderr": ["fatal: stream reset"], "exit": 101 }
+        }
+    });
+    let run = run_review(crash, &[("CODEX_TURN_RETRIES", "0")]);
+    assert_eq!(run.code(), Some(5));
+    assert!(run.stderr().contains("fatal: stream reset"));
+}